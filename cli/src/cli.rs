@@ -1,6 +1,7 @@
 use clap::{Arg, ArgAction, Command};
 
 /// Builds the CLI structure using `clap`.
+#[allow(dead_code)]
 pub fn build_cli() -> Command {
     Command::new("dockem-rs")
         .about("Build Docker images only when changes are detected")
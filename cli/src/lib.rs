@@ -0,0 +1,45 @@
+//! Build Docker images only when changes are detected.
+//!
+//! `dockem` hashes the watched files, directories and Dockerfile of an image. If an image tagged
+//! with that hash already exists on the registry, the new tags are copied across on the registry,
+//! otherwise the image is built and pushed. The `dockem-rs` binary is a thin wrapper around this
+//! crate.
+//!
+//! # Example
+//! ```no_run
+//! use dockem::{build_docker_image, BuildDockerImageParams, BuildEvent, BuildOutcome};
+//! use std::sync::Arc;
+//!
+//! # async fn run() -> Result<(), dockem::DockemError> {
+//! let params = BuildDockerImageParams::builder("my-org/backend")
+//!     .directory("./apps/backend")
+//!     .dockerfile_path("./apps/backend/Dockerfile")
+//!     .tag("dev")
+//!     .on_event(|event: &BuildEvent| println!("{:?}", event))
+//!     .build()?;
+//!
+//! let result = build_docker_image(Arc::new(params)).await?;
+//! if result.outcome == BuildOutcome::Reused {
+//!     println!("{} was already built", result.hashed_image_name);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub(crate) mod utils;
+
+pub use utils::{
+    build_docker_image, compute_image_hash, compute_image_hash_with_base_images, init_logging,
+    init_logging_with_writer, lint_dockerfile, resolve_base_images, BuildBackend, BuildBackendKind,
+    BuildCache, BuildDockerImageParams, BuildDockerImageParamsBuilder, BuildEvent, BuildOutcome,
+    BuildOutput, BuildPhase, BuildResult, BuildSecret, BuildctlBuildBackend, BuiltImage,
+    DaemonBuildBackend, DockemError, DockerEndpoint, Dockerfile, EventEmitter, EventHandler,
+    HashCache, HashInput, HashInputKind, HashManifest, HashScheme, HashSource, ImageHash,
+    JsonVersionFile, LintFinding, LintRule, LintSeverity, LogFormat, ManifestDiff,
+    RegistryErrorPolicy, ResolvedBaseImage, StaticVersion, VersionSource,
+    DEFAULT_HASH_CACHE_DIRECTORY,
+};
+
+// Validation helpers for the `dockem-rs` binary, they are not part of the stable API.
+#[doc(hidden)]
+pub use utils::{assert_directory_exists, assert_file_exists, assert_string_not_empty};
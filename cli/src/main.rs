@@ -1,9 +1,20 @@
 mod cli;
-mod utils;
+mod progress;
 
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{ArgAction, Parser, Subcommand};
+use dockem::{
+    compute_image_hash_with_base_images, lint_dockerfile, resolve_base_images,
+    BuildDockerImageParams, BuildDockerImageParamsBuilder, DockemError, EventEmitter,
+    JsonVersionFile, LintSeverity, LogFormat,
+};
+use progress::ProgressRenderer;
+use std::path::PathBuf;
+use std::process::ExitCode;
 use std::sync::Arc;
+use tracing::{error, info};
+
+/// The image name the `hash` command builds its parameters with, it does not affect the hash.
+const HASH_IMAGE_NAME: &str = "dockem-rs/hash";
 
 #[derive(Parser)]
 #[command(name = "dockem-rs")]
@@ -11,26 +22,192 @@ use std::sync::Arc;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Log more detail, use -v for debug and -vv for trace output
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Log less detail, use -q for warnings only, -qq for errors only and -qqq for nothing
+    #[arg(short, long, action = ArgAction::Count, global = true, conflicts_with = "verbose")]
+    quiet: u8,
+
+    /// The format of the log output: `text` or `json`
+    #[arg(long, default_value_t = LogFormat::Text, global = true)]
+    log_format: LogFormat,
+
+    /// Do not draw progress bars, they are only drawn when stderr is a terminal
+    #[arg(long, global = true)]
+    no_progress: bool,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    Build(BuildArgs),
+    Build(Box<BuildArgs>),
+    /// Print the content hash that `build` would tag the image with, use --verbose to list its inputs
+    Hash(HashArgs),
+    /// Check the Dockerfile for common problems, exits with an error code if any errors are found
+    Lint(LintArgs),
     Version,
 }
 
+/// The inputs to the content hash, shared by the `build` and `hash` commands.
 #[derive(Parser)]
-struct BuildArgs {
+struct HashInputArgs {
     #[arg(short, long, default_value = "./")]
     directory: String,
 
-    #[arg(short, long, default_value = "./Dockerfile")]
+    #[arg(short = 'f', long, default_value = "./Dockerfile")]
+    dockerfile_path: String,
+
+    #[arg(short = 'I', long)]
+    ignore_build_directory: bool,
+
+    #[arg(short, long)]
+    watch_file: Vec<String>,
+
+    #[arg(short = 'W', long)]
+    watch_directory: Vec<String>,
+
+    /// Watch the sources of the COPY and ADD instructions in the Dockerfile instead of the whole build directory
+    #[arg(long)]
+    auto_watch: bool,
+
+    /// Do not reuse or store file digests in the hash cache under .dockem/cache in the project root
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Where directory contents are hashed from: `filesystem`, or `git` to hash only tracked files
+    #[arg(long, default_value_t = dockem::HashSource::Filesystem)]
+    hash_source: dockem::HashSource,
+
+    /// The version of the hashing algorithm: `v2`, or `v1` to keep the tags of older releases while migrating
+    #[arg(long, default_value_t = dockem::HashScheme::V2)]
+    hash_scheme: dockem::HashScheme,
+
+    /// Resolve the images in the FROM lines to their digests through the registry and include them in the hash
+    #[arg(long)]
+    track_base_images: bool,
+
+    /// The directory paths are hashed relative to, defaults to the git repository around the build directory
+    #[arg(long)]
+    project_root: Option<PathBuf>,
+
+    /// A secret for RUN --mount=type=secret, e.g. id=npmrc,src=~/.npmrc, it is left out of the hash
+    #[arg(long)]
+    secret: Vec<dockem::BuildSecret>,
+}
+
+impl HashInputArgs {
+    fn validate(&self) -> Result<(), DockemError> {
+        dockem::assert_directory_exists(&self.directory, Some("The directory '%s' does not exist. Please specify the path to the directory you would like to build.")).map_err(DockemError::Validation)?;
+        for secret in &self.secret {
+            dockem::assert_file_exists(
+                &secret.source.to_string_lossy(),
+                Some("The secret file '%s' does not exist."),
+            )
+            .map_err(DockemError::Validation)?;
+        }
+        dockem::assert_file_exists(&self.dockerfile_path, Some("The file '%s' does not exist. Please specify the path to the Dockerfile you would like to use to build the image.")).map_err(DockemError::Validation)
+    }
+
+    /// A builder with the hash inputs set, the `build` command sets the remaining options.
+    fn into_builder(
+        self,
+        image_name: String,
+        events: EventEmitter,
+    ) -> BuildDockerImageParamsBuilder {
+        let mut builder = BuildDockerImageParams::builder(image_name)
+            .directory(self.directory)
+            .dockerfile_path(self.dockerfile_path)
+            .ignore_build_directory(self.ignore_build_directory)
+            .auto_watch(self.auto_watch)
+            .hash_cache(!self.no_cache)
+            .hash_source(self.hash_source)
+            .hash_scheme(self.hash_scheme)
+            .track_base_images(self.track_base_images)
+            .events(events);
+        if let Some(project_root) = self.project_root {
+            builder = builder.project_root(project_root);
+        }
+        for file in self.watch_file {
+            builder = builder.watch_file(file);
+        }
+        for directory in self.watch_directory {
+            builder = builder.watch_directory(directory);
+        }
+        for secret in self.secret {
+            builder = builder.secret(secret);
+        }
+        builder
+    }
+}
+
+/// How registries are reached, shared by the `build` and `hash` commands.
+#[derive(Parser)]
+struct RegistryTlsArgs {
+    /// A PEM file with CA certificates to trust for registries, in addition to the system roots
+    #[arg(long)]
+    registry_ca_file: Option<PathBuf>,
+
+    /// A registry to reach over plain HTTP instead of HTTPS, e.g. localhost:5000
+    #[arg(long)]
+    insecure_registry: Vec<String>,
+}
+
+impl RegistryTlsArgs {
+    fn validate(&self) -> Result<(), DockemError> {
+        match &self.registry_ca_file {
+            Some(ca_file) => dockem::assert_file_exists(&ca_file.to_string_lossy(), Some("The registry CA file '%s' does not exist. Please specify the path to a PEM file with the CA certificates.")).map_err(DockemError::Validation),
+            None => Ok(()),
+        }
+    }
+
+    fn apply(self, mut builder: BuildDockerImageParamsBuilder) -> BuildDockerImageParamsBuilder {
+        if let Some(ca_file) = self.registry_ca_file {
+            builder = builder.registry_ca_file(ca_file);
+        }
+        for registry in self.insecure_registry {
+            builder = builder.insecure_registry(registry);
+        }
+        builder
+    }
+}
+
+#[derive(Parser)]
+struct HashArgs {
+    #[command(flatten)]
+    inputs: HashInputArgs,
+
+    #[command(flatten)]
+    tls: RegistryTlsArgs,
+
+    /// Print the hash and its inputs as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Parser)]
+struct LintArgs {
+    #[arg(short = 'f', long, default_value = "./Dockerfile")]
     dockerfile_path: String,
 
+    /// Print the findings as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Parser)]
+struct BuildArgs {
+    #[command(flatten)]
+    inputs: HashInputArgs,
+
+    #[command(flatten)]
+    tls: RegistryTlsArgs,
+
     #[arg(short, long)]
     image_name: String,
 
-    #[arg(short, long, default_value = "./package.json")]
+    #[arg(short = 'F', long, default_value = "./package.json")]
     version_file: String,
 
     #[arg(short, long, default_value = "docker.io")]
@@ -39,57 +216,203 @@ struct BuildArgs {
     #[arg(short, long)]
     tag: Vec<String>,
 
-    #[arg(short, long)]
+    #[arg(short = 'u', long)]
     docker_username: Option<String>,
 
-    #[arg(short, long)]
+    #[arg(short = 'p', long)]
     docker_password: Option<String>,
 
+    /// The Docker daemon to build with, e.g. tcp://build-host:2376, defaults to DOCKER_HOST or the current Docker context
+    #[arg(long)]
+    docker_host: Option<String>,
+
     #[arg(short, long)]
     latest: bool,
 
     #[arg(short, long)]
     main_version: bool,
 
-    #[arg(short = 'I', long)]
-    ignore_build_directory: bool,
+    /// Build with the FROM lines pinned to the digests that were hashed, implies --track-base-images
+    #[arg(long)]
+    pin_base_images: bool,
 
-    #[arg(short, long)]
-    watch_file: Vec<String>,
+    /// What builds the image: `daemon`, or `buildctl` to build with BuildKit and push straight to the registry
+    #[arg(long, default_value_t = dockem::BuildBackendKind::Daemon)]
+    build_backend: dockem::BuildBackendKind,
 
-    #[arg(short = 'W', long)]
-    watch_directory: Vec<String>,
+    /// Import the layers of earlier builds from a registry cache, without a value the buildcache tag of the image
+    #[arg(long, num_args = 0..=1, default_missing_value = "registry")]
+    cache_from: Vec<dockem::BuildCache>,
+
+    /// Export the layers of this build: `registry` for the buildcache tag of the image, `inline` or type=registry,ref=<image>
+    #[arg(long, num_args = 0..=1, default_missing_value = "registry")]
+    cache_to: Vec<dockem::BuildCache>,
+
+    /// Forward an SSH agent socket or key to RUN --mount=type=ssh, e.g. default for SSH_AUTH_SOCK, needs the buildctl backend
+    #[arg(long)]
+    ssh: Vec<String>,
+
+    /// Write the image to type=oci,dest=<directory> or type=docker-archive,dest=<file> instead of pushing it
+    #[arg(long)]
+    output: Option<dockem::BuildOutput>,
+
+    /// Push the built image through the Docker daemon instead of saving it and pushing it with the registry client
+    #[arg(long)]
+    daemon_push: bool,
+
+    /// The BuildKit daemon for the buildctl backend, e.g. tcp://buildkitd:1234, defaults to BUILDKIT_HOST
+    #[arg(long)]
+    buildkit_host: Option<String>,
+
+    /// What to do when the registry check fails for a reason other than a missing image: `abort` or `build`
+    #[arg(long, default_value_t = dockem::RegistryErrorPolicy::Abort)]
+    on_registry_error: dockem::RegistryErrorPolicy,
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     let cli = Cli::parse();
+    // Clamp the counts so that a long run of -v or -q flags cannot overflow
+    let verbosity = cli.verbose.min(8) as i8 - cli.quiet.min(8) as i8;
+    let events = if !cli.no_progress && verbosity >= 0 && cli.log_format == LogFormat::Text {
+        let renderer = Arc::new(ProgressRenderer::default());
+        let writer = renderer.log_writer();
+        dockem::init_logging_with_writer(verbosity, cli.log_format, move || writer.clone());
+        EventEmitter::new(renderer)
+    } else {
+        dockem::init_logging(verbosity, cli.log_format);
+        EventEmitter::default()
+    };
+
+    let verbose = cli.verbose > 0;
+    match run(cli.command, events, verbose).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            error!("{}", error);
+            ExitCode::from(error.exit_code())
+        }
+    }
+}
 
-    match cli.command {
+async fn run(command: Commands, events: EventEmitter, verbose: bool) -> Result<(), DockemError> {
+    match command {
         Commands::Build(args) => {
             // Validate required paths
-            utils::assert_directory_exists(&args.directory, Some("ERROR: The directory '%s' does not exist. Please specify the path to the directory you would like to build.")).expect("");
-            utils::assert_file_exists(&args.dockerfile_path, Some("ERROR: The file '%s' does not exist. Please specify the path to the Dockerfile you would like to use to build the image.")).expect("");
-            utils::assert_file_exists(&args.version_file, Some("ERROR: The image-name flag is required. Please specify the name of the image you would like to build, this usually includes the organisation or group as well eg. your-org/image-name.")).expect("");
+            args.inputs.validate()?;
+            args.tls.validate()?;
+            dockem::assert_file_exists(&args.version_file, Some("The version file '%s' does not exist. Please specify the path to a JSON file with a 'version' key.")).map_err(DockemError::Validation)?;
+            dockem::assert_string_not_empty(&args.image_name, "--image-name", Some("The image-name flag is required. Please specify the name of the image you would like to build, this usually includes the organisation or group as well eg. your-org/image-name.")).map_err(DockemError::Validation)?;
 
             // Build the Docker image
-            let build_params = utils::BuildDockerImageParams {
-                directory: args.directory,
-                dockerfile_path: args.dockerfile_path,
-                image_name: args.image_name,
-                version_file: args.version_file,
-                registry: args.registry,
-                tag: args.tag,
-                docker_username: args.docker_username,
-                docker_password: args.docker_password,
-                latest: args.latest,
-                main_version: args.main_version,
-                ignore_build_directory: args.ignore_build_directory,
-                watch_file: Some(args.watch_file),
-                watch_directory: Some(args.watch_directory),
-            };
-
-            utils::build_docker_image(Arc::from(build_params)).await?;
+            let mut builder = args
+                .inputs
+                .into_builder(args.image_name, events)
+                .version_source(JsonVersionFile::new(args.version_file))
+                .registry(args.registry)
+                .registry_error_policy(args.on_registry_error)
+                .build_backend(args.build_backend)
+                .daemon_push(args.daemon_push)
+                .latest(args.latest)
+                .main_version(args.main_version)
+                .pin_base_images(args.pin_base_images);
+            for tag in args.tag {
+                builder = builder.tag(tag);
+            }
+            if let Some(username) = args.docker_username {
+                builder = builder.docker_username(username);
+            }
+            if let Some(password) = args.docker_password {
+                builder = builder.docker_password(password);
+            }
+            if let Some(docker_host) = args.docker_host {
+                builder = builder.docker_host(docker_host);
+            }
+            if let Some(buildkit_host) = args.buildkit_host {
+                builder = builder.buildkit_host(buildkit_host);
+            }
+            for ssh in args.ssh {
+                builder = builder.ssh(ssh);
+            }
+            if let Some(output) = args.output {
+                builder = builder.output(output);
+            }
+            for cache in args.cache_from {
+                builder = builder.cache_from(cache);
+            }
+            for cache in args.cache_to {
+                builder = builder.cache_to(cache);
+            }
+            let build_params = args.tls.apply(builder).build()?;
+
+            dockem::build_docker_image(Arc::from(build_params)).await?;
+        }
+        Commands::Hash(args) => {
+            args.inputs.validate()?;
+            args.tls.validate()?;
+            let builder = args
+                .inputs
+                .into_builder(HASH_IMAGE_NAME.to_string(), events);
+            let params = args.tls.apply(builder).build()?;
+            let base_images = resolve_base_images(&params).await?;
+            let image_hash = tokio::task::spawn_blocking(move || {
+                compute_image_hash_with_base_images(&params, &base_images)
+            })
+            .await
+            .map_err(|error| DockemError::Hash(error.into()))?
+            .map_err(DockemError::Hash)?;
+
+            if args.json {
+                let json = serde_json::to_string_pretty(&image_hash)
+                    .map_err(|error| DockemError::Hash(error.into()))?;
+                println!("{}", json);
+            } else {
+                if verbose {
+                    for input in &image_hash.inputs {
+                        println!("{}  {:<15}  {}", input.hash, input.kind, input.path);
+                    }
+                }
+                println!("{}", image_hash.hash);
+            }
+        }
+        Commands::Lint(args) => {
+            dockem::assert_file_exists(&args.dockerfile_path, Some("The file '%s' does not exist. Please specify the path to the Dockerfile you would like to lint.")).map_err(DockemError::Validation)?;
+            let dockerfile = dockem::Dockerfile::read(&args.dockerfile_path)
+                .map_err(|error| DockemError::Validation(format!("{:#}", error)))?;
+            let findings = lint_dockerfile(&dockerfile);
+
+            if args.json {
+                let json = serde_json::to_string_pretty(&findings)
+                    .map_err(|error| DockemError::Lint(error.to_string()))?;
+                println!("{}", json);
+            } else {
+                for finding in &findings {
+                    println!(
+                        "{}:{}  {:<7}  {:<20}  {}",
+                        args.dockerfile_path,
+                        finding.line,
+                        finding.severity,
+                        finding.rule,
+                        finding.message
+                    );
+                }
+            }
+
+            let errors = findings
+                .iter()
+                .filter(|finding| finding.severity == LintSeverity::Error)
+                .count();
+            info!(
+                "Found {} errors and {} warnings in {}",
+                errors,
+                findings.len() - errors,
+                args.dockerfile_path
+            );
+            if errors > 0 {
+                return Err(DockemError::Lint(format!(
+                    "The Dockerfile '{}' has {} lint errors",
+                    args.dockerfile_path, errors
+                )));
+            }
         }
         Commands::Version => {
             // Print the version of the application
@@ -99,3 +422,14 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::Cli;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition_is_valid() {
+        Cli::command().debug_assert();
+    }
+}
@@ -0,0 +1,139 @@
+use dockem::{BuildEvent, BuildPhase, EventHandler};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Renders `BuildEvent`s as progress bars on stderr. Nothing is drawn when stderr is not a
+/// terminal, so CI logs only contain the log lines.
+#[derive(Default)]
+pub struct ProgressRenderer {
+    multi: MultiProgress,
+    bars: Mutex<Bars>,
+}
+
+#[derive(Default)]
+struct Bars {
+    phase: Option<ProgressBar>,
+    build: Option<ProgressBar>,
+    push: HashMap<String, ProgressBar>,
+}
+
+impl ProgressRenderer {
+    /// A writer for the log output that clears the progress bars while a log line is written.
+    pub fn log_writer(&self) -> ProgressWriter {
+        ProgressWriter(self.multi.clone())
+    }
+
+    fn phase_name(phase: BuildPhase) -> &'static str {
+        match phase {
+            BuildPhase::Hash => "Hashing",
+            BuildPhase::Version => "Resolving the version",
+            BuildPhase::RegistryCheck => "Checking the registry",
+            BuildPhase::Build => "Building",
+            BuildPhase::Push => "Pushing",
+        }
+    }
+}
+
+impl EventHandler for ProgressRenderer {
+    fn on_event(&self, event: &BuildEvent) {
+        let mut bars = self.bars.lock().unwrap();
+        match event {
+            BuildEvent::PhaseStarted { phase } => {
+                let bar = self.multi.add(ProgressBar::new_spinner());
+                bar.set_style(ProgressStyle::with_template("{spinner} {msg} {elapsed}").unwrap());
+                bar.set_message(Self::phase_name(*phase));
+                bar.enable_steady_tick(Duration::from_millis(100));
+                bars.phase = Some(bar);
+            }
+            BuildEvent::PhaseFinished { phase, duration } => {
+                if let Some(bar) = bars.phase.take() {
+                    bar.finish_and_clear();
+                }
+                if *phase == BuildPhase::Build {
+                    if let Some(bar) = bars.build.take() {
+                        bar.finish_and_clear();
+                    }
+                }
+                self.multi
+                    .println(format!(
+                        "{} finished in {:.1}s",
+                        Self::phase_name(*phase),
+                        duration.as_secs_f64()
+                    ))
+                    .ok();
+            }
+            BuildEvent::CacheHit { image, .. } => {
+                self.multi
+                    .println(format!("{} already exists, reusing it", image))
+                    .ok();
+            }
+            BuildEvent::BuildStep {
+                step,
+                total,
+                instruction,
+            } => {
+                let bar = bars.build.get_or_insert_with(|| {
+                    let bar = self.multi.add(ProgressBar::new(u64::from(*total)));
+                    bar.set_style(
+                        ProgressStyle::with_template("Step {pos}/{len} [{bar:30}] {wide_msg}")
+                            .unwrap()
+                            .progress_chars("=> "),
+                    );
+                    bar
+                });
+                bar.set_length(u64::from(*total));
+                bar.set_position(u64::from(*step));
+                bar.set_message(instruction.clone());
+            }
+            BuildEvent::PushProgress {
+                image,
+                current,
+                total,
+            } => {
+                let bar = bars.push.entry(image.clone()).or_insert_with(|| {
+                    let bar = self.multi.add(ProgressBar::new(0));
+                    bar.set_style(
+                        ProgressStyle::with_template(
+                            "{msg} [{bar:30}] {bytes}/{total_bytes} {bytes_per_sec}",
+                        )
+                        .unwrap()
+                        .progress_chars("=> "),
+                    );
+                    bar.set_message(image.clone());
+                    bar
+                });
+                bar.set_length(total.unwrap_or(*current));
+                bar.set_position(*current);
+            }
+            BuildEvent::TagPushed { image } => {
+                if let Some(bar) = bars.push.remove(image) {
+                    bar.finish_and_clear();
+                }
+                self.multi.println(format!("Pushed {}", image)).ok();
+            }
+            BuildEvent::TagCopied { from, to } => {
+                self.multi
+                    .println(format!("Copied {} to {}", from, to))
+                    .ok();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Writes log lines to stderr without breaking the progress bars.
+#[derive(Clone)]
+pub struct ProgressWriter(MultiProgress);
+
+impl Write for ProgressWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.suspend(|| io::stderr().write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
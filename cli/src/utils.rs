@@ -15,48 +15,138 @@ pub use parse_version_file_json::*;
 
 mod assert_string_not_empty;
 pub use assert_string_not_empty::assert_string_not_empty;
+mod auto_watch_paths;
+pub use auto_watch_paths::*;
+mod hash_cache;
+pub use hash_cache::*;
+mod hash_directory_files;
+pub use hash_directory_files::*;
+mod hash_git_directory;
+pub use hash_git_directory::*;
+mod hash_scheme;
+pub use hash_scheme::*;
+mod hash_source;
+pub use hash_source::*;
+mod hash_manifest;
+pub use hash_manifest::*;
 mod hash_string;
 pub use hash_string::*;
 mod hash_watch_directories;
 pub use hash_watch_directories::*;
 mod hash_watch_files;
 pub use hash_watch_files::*;
+mod project_root;
+pub use project_root::*;
+mod unwatched_copy_sources;
+pub use unwatched_copy_sources::*;
 
 mod os_open;
 pub use os_open::*;
+mod parse_build_step;
+pub use parse_build_step::*;
+mod pin_base_images;
+pub use pin_base_images::*;
 
+mod build_backend;
+pub use build_backend::*;
+mod build_backend_kind;
+pub use build_backend_kind::*;
+mod build_cache;
+pub use build_cache::*;
 mod build_docker_image;
 pub use build_docker_image::*;
 mod build_docker_image_params;
 pub use build_docker_image_params::*;
+mod build_event;
+pub use build_event::*;
 mod build_image;
+mod buildctl_build_backend;
+pub use buildctl_build_backend::*;
+mod build_result;
+pub use build_result::*;
+mod build_output;
+pub use build_output::*;
+mod build_secret;
+pub use build_secret::*;
+mod build_to_output;
+pub use build_to_output::*;
+mod daemon_build_backend;
+pub use daemon_build_backend::*;
 
 mod build_log;
 pub use build_log::*;
 mod check_manifest_head;
 pub use check_manifest_head::*;
+mod check_blob_head;
+pub use check_blob_head::*;
+mod compute_image_hash;
+pub use compute_image_hash::*;
+mod registry_error_policy;
+pub use registry_error_policy::*;
+mod resolve_base_images;
+pub use resolve_base_images::*;
 
+mod create_build_backend;
+pub use create_build_backend::*;
 mod create_docker_client;
 pub use create_docker_client::*;
 mod create_regclient_client;
+mod create_registry_client;
+pub use create_registry_client::*;
+mod registry_auth_from_credentials;
+pub use registry_auth_from_credentials::*;
 
 mod copy_docker_image;
 pub use copy_docker_image::*;
 mod copy_existing_image_tag;
 pub use copy_existing_image_tag::*;
 
+mod dockem_error;
+mod dockerfile;
+pub use dockerfile::*;
 mod docker_config_loader;
+mod docker_endpoint;
+pub use dockem_error::*;
+pub use docker_endpoint::*;
+mod explain_rebuild;
+pub use explain_rebuild::*;
+mod fetch_hash_manifest;
+pub use fetch_hash_manifest::*;
 mod file_guard;
 pub use file_guard::*;
 mod generate_docker_image_name;
 pub use generate_docker_image_name::*;
 
+mod lint_dockerfile;
+pub use lint_dockerfile::*;
+
+mod init_logging;
+pub use init_logging::*;
+mod log_format;
+pub use log_format::*;
+
+mod compress_layer;
+pub use compress_layer::*;
+mod docker_archive;
+pub use docker_archive::*;
+mod oci_layout;
+pub use oci_layout::*;
+mod output_image_names;
+pub use output_image_names::*;
+mod push_docker_archive;
+pub use push_docker_archive::*;
+mod push_hash_manifest;
+pub use push_hash_manifest::*;
+mod save_docker_image;
+pub use save_docker_image::*;
+
 mod remove_empty_strings;
 pub use remove_empty_strings::*;
 
 mod tag_and_push_image;
 pub use tag_and_push_image::*;
-mod tag_and_push_images;
-pub use tag_and_push_images::*;
 mod tar_build_context;
 pub use tar_build_context::*;
+
+mod version_source;
+pub use version_source::*;
@@ -1,8 +1,8 @@
-use crate::assert_or_exit;
 use crate::utils::directory_exists;
+use crate::utils::macros::assert_or_exit;
 use std::path::Path;
 
-/// Asserts that a directory exists at the given path. If not, either exits or returns an error message.
+/// Asserts that a directory exists at the given path. If not, returns an error message.
 ///
 /// # Arguments
 /// * `path` - The path to the directory that should exist.
@@ -10,7 +10,7 @@ use std::path::Path;
 ///
 /// # Returns
 /// * `Ok(())` if the directory exists.
-/// * `Err(String)` containing the error message if the directory does not exist (only in test mode).
+/// * `Err(String)` containing the error message if the directory does not exist.
 pub fn assert_directory_exists(path: &str, error_message: Option<&str>) -> Result<(), String> {
     let default_message = "ERROR: The directory '%s' does not exist.";
     let error_message = error_message.unwrap_or(default_message);
@@ -1,9 +1,9 @@
-use crate::assert_or_exit;
+use crate::utils::macros::assert_or_exit;
 // Importing the macro
 use crate::utils::file_exists;
 use std::path::Path;
 
-/// Asserts that a file exists at the given path. If not, either exits or returns an error message.
+/// Asserts that a file exists at the given path. If not, returns an error message.
 ///
 /// # Arguments
 /// * `path` - The path to the file that should exist.
@@ -11,7 +11,7 @@ use std::path::Path;
 ///
 /// # Returns
 /// * `Ok(())` if the file exists.
-/// * `Err(String)` containing the error message if the file does not exist (only in test mode).
+/// * `Err(String)` containing the error message if the file does not exist.
 pub fn assert_file_exists(path: &str, error_message: Option<&str>) -> Result<(), String> {
     let default_message = "ERROR: The file '%s' does not exist.";
     let error_message = error_message.unwrap_or(default_message);
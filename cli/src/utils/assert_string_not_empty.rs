@@ -1,6 +1,6 @@
-use crate::assert_or_exit;
+use crate::utils::macros::assert_or_exit;
 
-/// Asserts that a string is not empty. If it is empty, either exits or returns an error message.
+/// Asserts that a string is not empty. If it is empty, returns an error message.
 ///
 /// # Arguments
 /// * `string` - The string to check the length of
@@ -9,7 +9,7 @@ use crate::assert_or_exit;
 ///
 /// # Returns
 /// * `Ok(())` if the string is not empty.
-/// * `Err(String)` containing the error message if the string is empty (only in test mode).
+/// * `Err(String)` containing the error message if the string is empty.
 pub fn assert_string_not_empty(
     string: &str,
     flag: &str,
@@ -21,7 +21,7 @@ pub fn assert_string_not_empty(
     let default_message = "ERROR: The string for flag '%s' does not exist.";
     let error_message = error_message.unwrap_or(default_message);
 
-    let output_message = error_message.replace("%s", &flag);
+    let output_message = error_message.replace("%s", flag);
 
     assert_or_exit!(!string.trim().is_empty(), output_message);
 }
@@ -0,0 +1,113 @@
+use crate::utils::{BuildDockerImageParams, Dockerfile};
+use anyhow::{anyhow, Result};
+use std::path::{Path, PathBuf};
+
+/// Derives the files and directories to watch from the `COPY` and `ADD` instructions of the
+/// Dockerfile, so that only what the image is built from is hashed instead of the whole build
+/// directory. Sources with wildcards are watched through the directory the wildcard is in, and
+/// paths inside another watched directory are left out.
+///
+/// # Arguments
+/// * `params` - The build parameters naming the Dockerfile and build directory.
+///
+/// # Returns
+/// * `Ok((Vec<String>, Vec<String>))` containing the files and the directories to watch, as paths
+///   inside the build directory.
+/// * `Err(anyhow::Error)` if the Dockerfile cannot be read, or a source is outside the build
+///   directory or does not exist.
+pub fn auto_watch_paths(params: &BuildDockerImageParams) -> Result<(Vec<String>, Vec<String>)> {
+    let dockerfile = Dockerfile::read(&params.dockerfile_path)?;
+
+    let mut paths: Vec<(PathBuf, String)> = Vec::new();
+    for source in dockerfile.copy_sources() {
+        let path = source.context_path().ok_or_else(|| {
+            anyhow!(
+                "The {} source '{}' on line {} is outside the build directory",
+                source.keyword,
+                source.source,
+                source.line
+            )
+        })?;
+        let description = format!(
+            "The {} source '{}' on line {}",
+            source.keyword, source.source, source.line
+        );
+        paths.push((path, description));
+    }
+
+    // Shorter paths first so that anything inside an already watched directory can be skipped
+    paths.sort_by_key(|(path, _)| path.components().count());
+    let mut files = Vec::new();
+    let mut directories: Vec<PathBuf> = Vec::new();
+    for (path, description) in paths {
+        if directories
+            .iter()
+            .chain(files.iter())
+            .any(|watched| path.starts_with(watched))
+        {
+            continue;
+        }
+
+        let full_path = Path::new(&params.directory).join(&path);
+        if full_path.is_dir() {
+            directories.push(path);
+        } else if full_path.exists() {
+            files.push(path);
+        } else {
+            return Err(anyhow!(
+                "{} does not exist in the build directory",
+                description
+            ));
+        }
+    }
+
+    let to_strings = |paths: Vec<PathBuf>| -> Vec<String> {
+        paths
+            .into_iter()
+            .map(|path| {
+                Path::new(&params.directory)
+                    .join(path)
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect()
+    };
+    Ok((to_strings(files), to_strings(directories)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{auto_watch_paths, BuildDockerImageParams};
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_watches_are_derived_from_copy_sources() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let context = temp_dir.path();
+        fs::create_dir_all(context.join("src/lib")).unwrap();
+        fs::create_dir_all(context.join("node_modules")).unwrap();
+        fs::write(context.join("package.json"), "{}").unwrap();
+        fs::write(context.join("src/lib/index.js"), "").unwrap();
+        fs::write(
+            context.join("Dockerfile"),
+            "FROM node:20 AS build\n\
+             COPY package.json ./\n\
+             COPY src/lib/index.js ./lib/\n\
+             COPY ./src/*.js ./src/\n\
+             FROM nginx\n\
+             COPY --from=build /app /srv\n",
+        )
+        .unwrap();
+        let path = |relative: &str| context.join(relative).to_str().unwrap().to_string();
+        let params = BuildDockerImageParams::builder("my-org/app")
+            .directory(path(""))
+            .dockerfile_path(path("Dockerfile"))
+            .build()
+            .unwrap();
+
+        let (files, directories) = auto_watch_paths(&params).unwrap();
+        assert_eq!(files, vec![path("package.json")]);
+        assert_eq!(directories, vec![path("src")]);
+    }
+}
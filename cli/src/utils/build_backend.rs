@@ -0,0 +1,47 @@
+use crate::utils::{BuildDockerImageParams, BuildLog, ResolvedBaseImage};
+use anyhow::Result;
+use futures_util::future::BoxFuture;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Where a `BuildBackend` left the image it built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuiltImage {
+    /// The image is in the Docker daemon's image store under this tag and still has to be pushed.
+    Local(String),
+    /// The image was written to this `docker save` archive, for a build with a `BuildOutput`.
+    Archive(PathBuf),
+    /// The image was pushed straight to the registry under the hashed image name, other tags are
+    /// copied on the registry.
+    Pushed,
+}
+
+/// Builds an image from the build directory and Dockerfile in the parameters. The backends are
+/// `DaemonBuildBackend`, which uses the Docker daemon, and `BuildctlBuildBackend`, which uses
+/// BuildKit.
+pub trait BuildBackend: Send + Sync {
+    /// A short name for logs, e.g. `daemon`.
+    fn name(&self) -> &'static str;
+
+    /// Builds the image and emits a `BuildEvent::BuildStep` for every step.
+    ///
+    /// # Arguments
+    /// * `params` - The build parameters naming the build directory and Dockerfile.
+    /// * `image_hash` - The content hash of the image.
+    /// * `image_name` - The hashed image name on the registry, e.g. `eu.reg.io/my-org/app:h2-…`.
+    /// * `base_images` - The resolved base images, the Dockerfile is pinned to them with
+    ///   `params.pin_base_images`.
+    /// * `build_log` - A shared, mutable reference to the build log.
+    ///
+    /// # Returns
+    /// * `Ok(BuiltImage)` saying where the image is now.
+    /// * `Err(anyhow::Error)` if the build failed.
+    fn build<'a>(
+        &'a self,
+        params: &'a BuildDockerImageParams,
+        image_hash: &'a str,
+        image_name: &'a str,
+        base_images: &'a [ResolvedBaseImage],
+        build_log: Arc<Mutex<BuildLog>>,
+    ) -> BoxFuture<'a, Result<BuiltImage>>;
+}
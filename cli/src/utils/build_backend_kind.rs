@@ -0,0 +1,36 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Decides which `BuildBackend` builds the image.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BuildBackendKind {
+    /// Build through the `/build` endpoint of the Docker daemon and push with the daemon.
+    #[default]
+    Daemon,
+    /// Build with `buildctl` against a BuildKit daemon and push straight to the registry.
+    Buildctl,
+}
+
+impl FromStr for BuildBackendKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "daemon" => Ok(BuildBackendKind::Daemon),
+            "buildctl" => Ok(BuildBackendKind::Buildctl),
+            _ => Err(format!(
+                "Unknown build backend '{}', expected 'daemon' or 'buildctl'",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for BuildBackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildBackendKind::Daemon => write!(f, "daemon"),
+            BuildBackendKind::Buildctl => write!(f, "buildctl"),
+        }
+    }
+}
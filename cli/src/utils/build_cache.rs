@@ -0,0 +1,107 @@
+use crate::utils::generate_docker_image_name;
+use std::fmt;
+use std::str::FromStr;
+
+/// The tag of the cache image that is used when a registry cache is given without a reference.
+pub const DEFAULT_BUILD_CACHE_TAG: &str = "buildcache";
+
+/// Where the layers of earlier builds are imported from or exported to, so that a rebuild on an
+/// ephemeral runner does not start cold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildCache {
+    /// A cache image on a registry. Without a reference it is the `buildcache` tag in the
+    /// repository of the image, e.g. `docker.io/my-org/app:buildcache`.
+    Registry(Option<String>),
+    /// Cache metadata written into the pushed image itself. This can only be exported, the image
+    /// it was pushed with is imported as a `Registry` cache.
+    Inline,
+}
+
+impl BuildCache {
+    /// Returns the reference of the cache image, or `None` for an inline cache.
+    ///
+    /// # Arguments
+    /// * `registry` - The registry the image is pushed to.
+    /// * `image_name` - The name of the image, e.g. `my-org/app`.
+    ///
+    /// # Returns
+    /// * `Option<String>` containing the full reference of the cache image.
+    pub fn reference(&self, registry: &str, image_name: &str) -> Option<String> {
+        match self {
+            BuildCache::Registry(Some(reference)) => Some(reference.clone()),
+            BuildCache::Registry(None) => Some(generate_docker_image_name(
+                registry,
+                image_name,
+                DEFAULT_BUILD_CACHE_TAG,
+            )),
+            BuildCache::Inline => None,
+        }
+    }
+}
+
+impl FromStr for BuildCache {
+    type Err = String;
+
+    /// Parses `registry`, `inline`, an image reference, or the `type=registry,ref=...` and
+    /// `type=inline` forms of `docker buildx build --cache-from`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "" | "registry" | "type=registry" => return Ok(BuildCache::Registry(None)),
+            "inline" | "type=inline" => return Ok(BuildCache::Inline),
+            reference if !reference.contains('=') => {
+                return Ok(BuildCache::Registry(Some(reference.to_string())))
+            }
+            _ => {}
+        }
+
+        let mut reference = None;
+        for field in value.split(',') {
+            match field.split_once('=') {
+                Some(("type", "registry")) => {}
+                Some(("ref", value)) if !value.is_empty() => reference = Some(value.to_string()),
+                _ => {
+                    return Err(format!(
+                        "Invalid cache '{}', expected registry, inline, an image reference or type=registry,ref=<image>",
+                        value
+                    ))
+                }
+            }
+        }
+        Ok(BuildCache::Registry(reference))
+    }
+}
+
+impl fmt::Display for BuildCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildCache::Registry(Some(reference)) => write!(f, "type=registry,ref={}", reference),
+            BuildCache::Registry(None) => write!(f, "registry"),
+            BuildCache::Inline => write!(f, "inline"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::BuildCache;
+
+    #[test]
+    fn test_caches_default_to_the_image_repository() {
+        let parse = |value: &str| value.parse::<BuildCache>().unwrap();
+
+        assert_eq!(
+            parse("registry").reference("eu.reg.io", "my-org/app"),
+            Some("eu.reg.io/my-org/app:buildcache".to_string())
+        );
+        assert_eq!(
+            parse("type=registry,ref=eu.reg.io/cache:app").reference("eu.reg.io", "my-org/app"),
+            Some("eu.reg.io/cache:app".to_string())
+        );
+        assert_eq!(
+            parse("eu.reg.io/my-org/app:latest"),
+            BuildCache::Registry(Some("eu.reg.io/my-org/app:latest".to_string()))
+        );
+        assert_eq!(parse("type=inline"), BuildCache::Inline);
+        assert!("type=local,dest=/tmp".parse::<BuildCache>().is_err());
+    }
+}
@@ -1,16 +1,22 @@
-use crate::utils::build_image::build_image;
 use crate::utils::create_regclient_client::create_regclient_client;
-use crate::utils::extract_version::extract_version;
 use crate::utils::{
-    check_manifest_head, copy_existing_image_tag, create_docker_client, generate_docker_image_name,
-    hash_directory, hash_file, hash_string, hash_watch_directories, hash_watch_files,
-    remove_empty_strings, tag_and_push_image, tag_and_push_new_images,
+    build_to_output, check_manifest_head, compute_image_hash_with_base_images,
+    copy_existing_image_tag, create_build_backend, create_registry_client, explain_rebuild,
+    generate_docker_image_name, previous_image_name, push_docker_archive, push_hash_manifest,
+    registry_auth_from_credentials, remove_empty_strings, resolve_base_images,
+    save_docker_image_to_temporary_archive, tag_and_push_image,
+};
+use crate::utils::{
+    BuildBackendKind, BuildCache, BuildDockerImageParams, BuildEvent, BuildLog, BuildPhase,
+    BuildResult, BuiltImage, DockemError, DockerArchive, HashScheme, ImageHash, ManifestStatus,
+    RegistryErrorPolicy,
 };
-use crate::utils::{BuildDockerImageParams, BuildLog};
 use anyhow::{anyhow, Context, Result};
 use oci_client::secrets::RegistryAuth;
+use oci_client::RegistryOperation;
 use std::sync::{Arc, Mutex};
 use tokio::task;
+use tracing::{error, info, info_span, warn, Instrument};
 
 /// Builds a Docker image or reuses an existing one based on content hashing.
 ///
@@ -18,9 +24,13 @@ use tokio::task;
 /// * `params` - The parameters for building the Docker image.
 ///
 /// # Returns
-/// * `BuildLog` containing metadata about the build process.
-pub async fn build_docker_image(params: Arc<BuildDockerImageParams>) -> Result<BuildLog> {
+/// * `BuildResult` describing the hash, version and tags of the image.
+/// * `DockemError` describing which step of the process failed.
+pub async fn build_docker_image(
+    params: Arc<BuildDockerImageParams>,
+) -> Result<BuildResult, DockemError> {
     let mut build_log = BuildLog::default();
+    let events = params.events.clone();
 
     // Create a cleaned version of the parameters
     let cleaned_params = {
@@ -28,48 +38,83 @@ pub async fn build_docker_image(params: Arc<BuildDockerImageParams>) -> Result<B
         remove_empty_strings(&mut params_clone.tag);
         params_clone
     };
+    if cleaned_params.build_backend == BuildBackendKind::Daemon
+        && !(cleaned_params.secrets.is_empty() && cleaned_params.ssh.is_empty())
+    {
+        return Err(DockemError::Validation(
+            "Build secrets and SSH forwarding need BuildKit, build with --build-backend=buildctl"
+                .to_string(),
+        ));
+    }
+    if cleaned_params.cache_from.contains(&BuildCache::Inline) {
+        return Err(DockemError::Validation(
+            "An inline cache can only be exported, import the image it was pushed with instead"
+                .to_string(),
+        ));
+    }
+    if cleaned_params.build_backend == BuildBackendKind::Daemon
+        && cleaned_params
+            .cache_to
+            .iter()
+            .any(|cache| cache != &BuildCache::Inline)
+    {
+        return Err(DockemError::Validation(
+            "Exporting a registry cache needs BuildKit, build with --build-backend=buildctl"
+                .to_string(),
+        ));
+    }
+
+    if cleaned_params.output.is_some() && cleaned_params.daemon_push {
+        return Err(DockemError::Validation(
+            "An image written to an output is not pushed, remove --daemon-push".to_string(),
+        ));
+    }
 
     // Use the cleaned parameters for the rest of the function
     let docker_username = cleaned_params.docker_username.as_deref().unwrap_or("");
     let docker_password = cleaned_params.docker_password.as_deref().unwrap_or("");
 
     // Compute overall hash in a blocking thread
-    let watch_file_and_dir_hash = task::spawn_blocking({
+    let started = events.phase_started(BuildPhase::Hash);
+    let base_images = resolve_base_images(&cleaned_params)
+        .instrument(info_span!("base_images"))
+        .await?;
+    build_log.base_images = base_images.clone();
+    let image_hash = task::spawn_blocking({
         let cleaned_params_clone = cleaned_params.clone();
-        move || -> Result<String> {
-            let mut hash_accumulator = String::new();
-
-            if let Some(watch_files) = &cleaned_params_clone.watch_file {
-                hash_accumulator.push_str(&hash_watch_files(watch_files)?);
-            }
-
-            if let Some(watch_dirs) = &cleaned_params_clone.watch_directory {
-                hash_accumulator.push_str(&hash_watch_directories(watch_dirs)?);
-            }
-
-            if !cleaned_params_clone.ignore_build_directory {
-                hash_accumulator.push_str(&hash_directory(&cleaned_params_clone.directory)?);
-            }
-
-            hash_accumulator.push_str(&hash_file(&cleaned_params_clone.dockerfile_path)?);
-            Ok(hash_string(&hash_accumulator))
+        let base_images = base_images.clone();
+        let span = info_span!("hash");
+        move || -> Result<ImageHash> {
+            let _entered = span.enter();
+            let image_hash =
+                compute_image_hash_with_base_images(&cleaned_params_clone, &base_images)?;
+            info!("Computed the image hash {}", image_hash.hash);
+            Ok(image_hash)
         }
     })
     .await
-    .context("Failed to compute overall hash")??;
-    build_log.image_hash = watch_file_and_dir_hash.clone();
+    .context("Failed to compute overall hash")
+    .and_then(|result| result)
+    .map_err(DockemError::Hash)?;
+    events.phase_finished(BuildPhase::Hash, started);
+    build_log.image_hash = image_hash.hash.clone();
 
-    // Extract version from version file
+    // Resolve the version from the version source
+    let started = events.phase_started(BuildPhase::Version);
     let version = task::spawn_blocking({
-        let cleaned_params_clone = cleaned_params.clone();
-        println!(
-            "Extracting version from file {}",
-            cleaned_params_clone.version_file
-        );
-        move || -> Result<String> { Ok(extract_version(&cleaned_params_clone.version_file)?) }
+        let version_source = cleaned_params.version_source.clone();
+        let span = info_span!("version", source = ?version_source);
+        move || -> Result<String, DockemError> {
+            let _entered = span.enter();
+            info!("Extracting version from {:?}", version_source);
+            version_source.version()
+        }
     })
     .await
-    .context("Failed to extract version from file".to_string())??; // Handle JoinError and Result
+    .context("Failed to extract version")
+    .map_err(DockemError::Version)
+    .and_then(|result| result)?; // Handle JoinError and Result
+    events.phase_finished(BuildPhase::Version, started);
     build_log.version = version.clone();
 
     // Generate the hashed image name
@@ -80,28 +125,76 @@ pub async fn build_docker_image(params: Arc<BuildDockerImageParams>) -> Result<B
     );
     build_log.hashed_image_name = image_name.clone();
 
-    let (registry_client, reference) = match create_regclient_client(
-        &cleaned_params.registry,
-        &docker_username,
-        &docker_password,
-        &image_name,
-        &mut build_log,
-    )
-    .await
-    {
-        Ok((registry_client, reference)) => (registry_client, reference),
-        Err(e) => {
-            eprintln!("Failed to create registry client: {}", e);
-            return Err(anyhow!("Failed to create registry client"));
-        }
-    };
+    // An output takes the place of the registry
+    if let Some(output) = &cleaned_params.output {
+        return build_to_output(&cleaned_params, output, &version, &base_images, build_log).await;
+    }
 
     // Check if image already exists
-    if check_manifest_head(&image_name, reference, &registry_client).await {
-        println!(
+    let started = events.phase_started(BuildPhase::RegistryCheck);
+    let (registry_client, reference, image_exists) = async {
+        let (registry_client, reference) = create_regclient_client(
+            create_registry_client(&cleaned_params)?,
+            &cleaned_params.registry,
+            docker_username,
+            docker_password,
+            &image_name,
+            &mut build_log,
+        )
+        .await?;
+
+        let image_exists =
+            match check_manifest_head(&image_name, &reference, &registry_client).await {
+                ManifestStatus::Exists { digest } => {
+                    info!(
+                        "Found the image hash {} with digest {}.",
+                        image_name, digest
+                    );
+                    events.emit(BuildEvent::CacheHit {
+                        image: image_name.clone(),
+                        digest,
+                    });
+                    true
+                }
+                ManifestStatus::NotFound => false,
+                ManifestStatus::Error(error) => match cleaned_params.registry_error_policy {
+                    RegistryErrorPolicy::Abort => {
+                        error!(
+                            "Unable to check the registry for the image hash {}.",
+                            image_name
+                        );
+                        return Err(DockemError::from_registry_error(error));
+                    }
+                    RegistryErrorPolicy::Build => {
+                        warn!(
+                            "Unable to check the registry for the image hash {}: {}",
+                            image_name, error
+                        );
+                        warn!("The build will continue, but this should be investigated.");
+                        false
+                    }
+                },
+            };
+
+        // Explain the rebuild while the registry is being asked anyway, v1 has no file hashes
+        if !image_exists && image_hash.scheme != HashScheme::V1 {
+            let previous_image = previous_image_name(&cleaned_params, &version);
+            build_log.manifest_diff =
+                explain_rebuild(&image_hash.manifest, &previous_image, &registry_client).await;
+        }
+        Ok((registry_client, reference, image_exists))
+    }
+    .instrument(info_span!("registry_check", image = %image_name))
+    .await?;
+    events.phase_finished(BuildPhase::RegistryCheck, started);
+    build_log.hash_exists = image_exists;
+
+    if image_exists {
+        info!(
             "Image {} already exists on the registry. Copying tags...",
             image_name
         );
+        let started = events.phase_started(BuildPhase::Push);
         copy_existing_image_tag(
             &cleaned_params,
             &version,
@@ -110,49 +203,142 @@ pub async fn build_docker_image(params: Arc<BuildDockerImageParams>) -> Result<B
             &RegistryAuth::Anonymous,
             &mut build_log,
         )
-        .await?;
+        .instrument(info_span!("push", image = %image_name))
+        .await
+        .map_err(DockemError::Push)?;
+        events.phase_finished(BuildPhase::Push, started);
     } else {
-        println!(
+        info!(
             "Image {} does not exist on the registry. Building and pushing...",
             image_name
         );
 
-        // Create Docker client
-        let (docker_client, docker_credentials) = create_docker_client(
-            Some(docker_username),
-            Some(docker_password),
-            &cleaned_params.registry,
-        )
-        .await
-        .map_err(|error| anyhow!(error.to_string()))?;
-        println!("Docker client authenticated successfully.");
+        let started = events.phase_started(BuildPhase::Build);
+        let (built_image, docker) = async {
+            let (backend, docker) = create_build_backend(&cleaned_params).await?;
 
-        // Build the image
-        let local_tag = build_image(
-            &docker_client,
-            &cleaned_params,
-            &build_log.image_hash,
-            Arc::new(Mutex::new(build_log.clone())), // Clone build_log to avoid moving it
-        )
+            // Build the image
+            info!("Building with the {} backend", backend.name());
+            let built_image = backend
+                .build(
+                    &cleaned_params,
+                    &build_log.image_hash,
+                    &image_name,
+                    &base_images,
+                    Arc::new(Mutex::new(build_log.clone())), // Clone build_log to avoid moving it
+                )
+                .await
+                .map_err(DockemError::Build)?;
+            Ok::<_, DockemError>((built_image, docker))
+        }
+        .instrument(info_span!("build", image = %image_name))
         .await?;
-        build_log.local_tag = local_tag.clone();
+        events.phase_finished(BuildPhase::Build, started);
 
-        println!("Docker build complete. Pushing image...");
+        let started = events.phase_started(BuildPhase::Push);
+        async {
+            match (built_image, docker) {
+                (BuiltImage::Local(local_tag), Some((docker_client, docker_credentials)))
+                    if !cleaned_params.daemon_push =>
+                {
+                    build_log.local_tag = Some(local_tag.clone());
+                    info!("Docker build complete. Saving the image to push it...");
 
-        // Tag and push the hashed image
-        tag_and_push_image(&docker_client, &local_tag, &image_name, &docker_credentials).await?;
-        println!("Image {} pushed to registry.", image_name);
+                    // Save the image and push it without the daemon
+                    let archive_path = save_docker_image_to_temporary_archive(
+                        &docker_client,
+                        &local_tag,
+                        &build_log.image_hash,
+                    )
+                    .await
+                    .map_err(DockemError::Push)?;
+                    let archive = task::spawn_blocking({
+                        let archive_path = archive_path.to_path_buf();
+                        move || DockerArchive::open(archive_path)
+                    })
+                    .await
+                    .context("Failed to read the saved image")
+                    .and_then(|result| result)
+                    .map_err(DockemError::Push)?;
 
-        // Tag and push additional images
-        tag_and_push_new_images(
-            &docker_client,
-            &cleaned_params,
-            &version,
-            &local_tag,
-            &docker_credentials,
-            &mut build_log,
-        )
+                    push_docker_archive(
+                        &archive,
+                        &image_name,
+                        &registry_client,
+                        &docker_credentials,
+                        &events,
+                    )
+                    .await
+                    .map_err(DockemError::Push)?;
+                }
+                (BuiltImage::Local(local_tag), Some((docker_client, docker_credentials))) => {
+                    build_log.local_tag = Some(local_tag.clone());
+                    info!("Docker build complete. Pushing image...");
+
+                    // The tags are copied with the registry client, which needs the daemon's login
+                    let registry_auth = registry_auth_from_credentials(&docker_credentials)
+                        .map_err(DockemError::Push)?;
+                    registry_client
+                        .auth(&reference, &registry_auth, RegistryOperation::Push)
+                        .await
+                        .map_err(DockemError::from_registry_error)?;
+
+                    // Tag and push the hashed image
+                    tag_and_push_image(
+                        &docker_client,
+                        &local_tag,
+                        &image_name,
+                        &docker_credentials,
+                        &events,
+                    )
+                    .await
+                    .map_err(|error| DockemError::Push(error.into()))?;
+                }
+                (BuiltImage::Local(local_tag), None) => {
+                    return Err(DockemError::Push(anyhow!(
+                        "The image {} was built into a Docker daemon dockem is not connected to",
+                        local_tag
+                    )));
+                }
+                (BuiltImage::Archive(path), _) => {
+                    return Err(DockemError::Push(anyhow!(
+                        "The image was written to {} instead of being pushed",
+                        path.display()
+                    )));
+                }
+                (BuiltImage::Pushed, _) => {
+                    events.emit(BuildEvent::TagPushed {
+                        image: image_name.clone(),
+                    });
+                }
+            }
+            info!("Image {} pushed to registry. Copying tags...", image_name);
+
+            // Copy the other tags on the registry, like for an image that was already built
+            copy_existing_image_tag(
+                &cleaned_params,
+                &version,
+                &image_name,
+                &registry_client,
+                &RegistryAuth::Anonymous,
+                &mut build_log,
+            )
+            .await
+            .map_err(DockemError::Push)?;
+
+            // Store the hash manifest so the next build can explain why it rebuilt
+            if image_hash.scheme != HashScheme::V1 {
+                if let Err(error) =
+                    push_hash_manifest(&image_hash.manifest, &reference, &registry_client).await
+                {
+                    warn!("Unable to push the hash manifest: {:#}", error);
+                }
+            }
+            Ok::<_, DockemError>(())
+        }
+        .instrument(info_span!("push", image = %image_name))
         .await?;
+        events.phase_finished(BuildPhase::Push, started);
     }
-    Ok(build_log)
+    Ok(BuildResult::from(build_log))
 }
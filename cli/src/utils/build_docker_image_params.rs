@@ -1,17 +1,446 @@
+use crate::utils::{
+    BuildBackendKind, BuildCache, BuildOutput, BuildSecret, DockemError, EventEmitter,
+    EventHandler, HashScheme, HashSource, JsonVersionFile, RegistryErrorPolicy, VersionSource,
+    DEFAULT_HASH_CACHE_DIRECTORY,
+};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
 /// This struct is used to save CLI argument values passed into the program.
+/// Library users should create it with `BuildDockerImageParams::builder`.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct BuildDockerImageParams {
+    pub auto_watch: bool,
+    pub build_backend: BuildBackendKind,
+    pub buildkit_host: Option<String>,
+    pub cache_from: Vec<BuildCache>,
+    pub cache_to: Vec<BuildCache>,
+    pub daemon_push: bool,
     pub directory: String,
+    pub docker_host: Option<String>,
     pub docker_password: Option<String>,
     pub docker_username: Option<String>,
     pub dockerfile_path: String,
+    pub events: EventEmitter,
+    pub hash_cache_directory: Option<PathBuf>,
+    pub hash_scheme: HashScheme,
+    pub hash_source: HashSource,
     pub ignore_build_directory: bool,
     pub image_name: String,
+    pub insecure_registries: Vec<String>,
     pub latest: bool,
     pub main_version: bool,
+    pub output: Option<BuildOutput>,
+    pub pin_base_images: bool,
+    pub project_root: Option<PathBuf>,
     pub registry: String,
+    pub registry_ca_file: Option<PathBuf>,
+    pub registry_error_policy: RegistryErrorPolicy,
+    pub secrets: Vec<BuildSecret>,
+    pub ssh: Vec<String>,
     pub tag: Vec<String>,
-    pub version_file: String,
+    pub track_base_images: bool,
+    pub version_source: Arc<dyn VersionSource>,
     pub watch_directory: Option<Vec<String>>,
     pub watch_file: Option<Vec<String>>,
 }
+
+impl BuildDockerImageParams {
+    /// Starts building the parameters for the given image name, e.g. `your-org/image-name`.
+    /// Every other option uses the same default as the `dockem-rs build` command.
+    pub fn builder(image_name: impl Into<String>) -> BuildDockerImageParamsBuilder {
+        BuildDockerImageParamsBuilder::new(image_name)
+    }
+
+    /// Returns the paths of the secrets inside the given directory, relative to it. These are left
+    /// out of both the hash and the build context.
+    ///
+    /// # Arguments
+    /// * `directory` - The directory that is hashed or sent as the build context.
+    pub fn secrets_in(&self, directory: &str) -> Vec<PathBuf> {
+        self.secrets
+            .iter()
+            .filter_map(|secret| secret.relative_to(Path::new(directory)))
+            .collect()
+    }
+}
+
+/// Builder for `BuildDockerImageParams`.
+///
+/// # Example
+/// ```no_run
+/// use dockem::{BuildDockerImageParams, StaticVersion};
+///
+/// let params = BuildDockerImageParams::builder("my-org/backend")
+///     .directory("./apps/backend")
+///     .dockerfile_path("./apps/backend/Dockerfile")
+///     .registry("eu.reg.io")
+///     .tag("dev")
+///     .version_source(StaticVersion("v1.2.3".to_string()))
+///     .build()
+///     .expect("Invalid build parameters");
+/// ```
+#[derive(Debug)]
+pub struct BuildDockerImageParamsBuilder {
+    params: BuildDockerImageParams,
+}
+
+impl BuildDockerImageParamsBuilder {
+    fn new(image_name: impl Into<String>) -> Self {
+        Self {
+            params: BuildDockerImageParams {
+                auto_watch: false,
+                build_backend: BuildBackendKind::default(),
+                buildkit_host: None,
+                cache_from: Vec::new(),
+                cache_to: Vec::new(),
+                daemon_push: false,
+                directory: "./".to_string(),
+                docker_host: None,
+                docker_password: None,
+                docker_username: None,
+                dockerfile_path: "./Dockerfile".to_string(),
+                events: EventEmitter::default(),
+                hash_cache_directory: Some(DEFAULT_HASH_CACHE_DIRECTORY.into()),
+                hash_scheme: HashScheme::default(),
+                hash_source: HashSource::default(),
+                ignore_build_directory: false,
+                image_name: image_name.into(),
+                insecure_registries: Vec::new(),
+                latest: false,
+                main_version: false,
+                output: None,
+                pin_base_images: false,
+                project_root: None,
+                registry: "docker.io".to_string(),
+                registry_ca_file: None,
+                registry_error_policy: RegistryErrorPolicy::default(),
+                secrets: Vec::new(),
+                ssh: Vec::new(),
+                tag: Vec::new(),
+                track_base_images: false,
+                version_source: Arc::new(JsonVersionFile::new("./package.json")),
+                watch_directory: None,
+                watch_file: None,
+            },
+        }
+    }
+
+    /// The directory used as the Docker build context.
+    pub fn directory(mut self, directory: impl Into<String>) -> Self {
+        self.params.directory = directory.into();
+        self
+    }
+
+    /// The path to the Dockerfile.
+    pub fn dockerfile_path(mut self, dockerfile_path: impl Into<String>) -> Self {
+        self.params.dockerfile_path = dockerfile_path.into();
+        self
+    }
+
+    /// The registry to check, push and copy images on.
+    pub fn registry(mut self, registry: impl Into<String>) -> Self {
+        self.params.registry = registry.into();
+        self
+    }
+
+    /// A PEM file with CA certificates to trust for registries, in addition to the system
+    /// roots. It may contain several certificates.
+    pub fn registry_ca_file(mut self, ca_file: impl Into<PathBuf>) -> Self {
+        self.params.registry_ca_file = Some(ca_file.into());
+        self
+    }
+
+    /// Adds a registry, e.g. `localhost:5000`, that is reached over plain HTTP instead of HTTPS.
+    pub fn insecure_registry(mut self, registry: impl Into<String>) -> Self {
+        self.params.insecure_registries.push(registry.into());
+        self
+    }
+
+    /// The Docker daemon to build with, e.g. `tcp://build-host:2376`. By default it is taken
+    /// from `DOCKER_HOST` or the current Docker context, see `DockerEndpoint::resolve`.
+    pub fn docker_host(mut self, docker_host: impl Into<String>) -> Self {
+        self.params.docker_host = Some(docker_host.into());
+        self
+    }
+
+    /// Pushes the built image through the Docker daemon instead of saving it and pushing it with
+    /// the registry client. Disabled by default.
+    pub fn daemon_push(mut self, daemon_push: bool) -> Self {
+        self.params.daemon_push = daemon_push;
+        self
+    }
+
+    /// Writes the image to an OCI layout directory or a `docker load` archive instead of pushing
+    /// it, without asking the registry whether it was already built.
+    pub fn output(mut self, output: BuildOutput) -> Self {
+        self.params.output = Some(output);
+        self
+    }
+
+    /// What builds the image, the Docker daemon by default. `BuildBackendKind::Buildctl` builds
+    /// with BuildKit and pushes straight to the registry.
+    pub fn build_backend(mut self, build_backend: BuildBackendKind) -> Self {
+        self.params.build_backend = build_backend;
+        self
+    }
+
+    /// The BuildKit daemon `buildctl` connects to, e.g. `tcp://buildkitd:1234`. By default
+    /// `buildctl` uses `BUILDKIT_HOST` or its default socket.
+    pub fn buildkit_host(mut self, buildkit_host: impl Into<String>) -> Self {
+        self.params.buildkit_host = Some(buildkit_host.into());
+        self
+    }
+
+    /// Adds a cache that layers of earlier builds are imported from, e.g.
+    /// `BuildCache::Registry(None)` for the `buildcache` tag in the repository of the image.
+    pub fn cache_from(mut self, cache: BuildCache) -> Self {
+        self.params.cache_from.push(cache);
+        self
+    }
+
+    /// Adds a cache that the layers of the build are exported to. Only `BuildCache::Inline` is
+    /// supported by the daemon backend.
+    pub fn cache_to(mut self, cache: BuildCache) -> Self {
+        self.params.cache_to.push(cache);
+        self
+    }
+
+    /// Adds a secret that `RUN --mount=type=secret` instructions can mount. Secrets need the
+    /// buildctl backend and are never part of the hash.
+    pub fn secret(mut self, secret: BuildSecret) -> Self {
+        self.params.secrets.push(secret);
+        self
+    }
+
+    /// Forwards an SSH agent socket or key to `RUN --mount=type=ssh` instructions, e.g. `default`
+    /// for `SSH_AUTH_SOCK`. Needs the buildctl backend.
+    pub fn ssh(mut self, ssh: impl Into<String>) -> Self {
+        self.params.ssh.push(ssh.into());
+        self
+    }
+
+    /// Credentials for the registry and the Docker daemon.
+    pub fn credentials(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.params.docker_username = Some(username.into());
+        self.params.docker_password = Some(password.into());
+        self
+    }
+
+    /// The username for the registry and the Docker daemon, see `credentials`.
+    pub fn docker_username(mut self, username: impl Into<String>) -> Self {
+        self.params.docker_username = Some(username.into());
+        self
+    }
+
+    /// The password for the registry and the Docker daemon, see `credentials`.
+    pub fn docker_password(mut self, password: impl Into<String>) -> Self {
+        self.params.docker_password = Some(password.into());
+        self
+    }
+
+    /// Adds a tag, the version is appended to it, e.g. `dev` becomes `dev-v1.0.0`.
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.params.tag.push(tag.into());
+        self
+    }
+
+    /// Whether to also push the `latest` tag.
+    pub fn latest(mut self, latest: bool) -> Self {
+        self.params.latest = latest;
+        self
+    }
+
+    /// Whether to also push the bare version as a tag, e.g. `v1.0.0`.
+    pub fn main_version(mut self, main_version: bool) -> Self {
+        self.params.main_version = main_version;
+        self
+    }
+
+    /// Whether to leave the build directory out of the content hash.
+    pub fn ignore_build_directory(mut self, ignore_build_directory: bool) -> Self {
+        self.params.ignore_build_directory = ignore_build_directory;
+        self
+    }
+
+    /// Adds a file to the content hash.
+    pub fn watch_file(mut self, file: impl Into<String>) -> Self {
+        self.params
+            .watch_file
+            .get_or_insert_with(Vec::new)
+            .push(file.into());
+        self
+    }
+
+    /// Adds a directory to the content hash.
+    pub fn watch_directory(mut self, directory: impl Into<String>) -> Self {
+        self.params
+            .watch_directory
+            .get_or_insert_with(Vec::new)
+            .push(directory.into());
+        self
+    }
+
+    /// Watches the sources of the `COPY` and `ADD` instructions in the Dockerfile instead of the
+    /// whole build directory, in addition to any watch files and directories. Disabled by default.
+    pub fn auto_watch(mut self, auto_watch: bool) -> Self {
+        self.params.auto_watch = auto_watch;
+        self
+    }
+
+    /// Caches the digests of file contents in the given directory so that unchanged files are not
+    /// read again on the next run. A relative directory is taken relative to the project root,
+    /// the default is `.dockem/cache` there.
+    pub fn hash_cache_directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.params.hash_cache_directory = Some(directory.into());
+        self
+    }
+
+    /// Whether the hash cache is read and written, enabled by default like in the CLI. Turning it
+    /// off is the same as `--no-cache`.
+    pub fn hash_cache(mut self, hash_cache: bool) -> Self {
+        if !hash_cache {
+            self.params.hash_cache_directory = None;
+        } else if self.params.hash_cache_directory.is_none() {
+            self.params.hash_cache_directory = Some(DEFAULT_HASH_CACHE_DIRECTORY.into());
+        }
+        self
+    }
+
+    /// The directory paths are hashed relative to, so that the hash does not depend on where the
+    /// project is checked out. Defaults to the closest ancestor of the build directory that
+    /// contains `.git`, see `resolve_project_root`.
+    pub fn project_root(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.params.project_root = Some(directory.into());
+        self
+    }
+
+    /// The version of the hashing algorithm, `HashScheme::V2` by default. Use `HashScheme::V1` to
+    /// keep reusing images hashed by older releases while migrating.
+    pub fn hash_scheme(mut self, hash_scheme: HashScheme) -> Self {
+        self.params.hash_scheme = hash_scheme;
+        self
+    }
+
+    /// Resolves the base images in the `FROM` lines to their digests and includes them in the
+    /// hash, so that an update of a base image triggers a rebuild. Disabled by default.
+    pub fn track_base_images(mut self, track_base_images: bool) -> Self {
+        self.params.track_base_images = track_base_images;
+        self
+    }
+
+    /// Builds with the base images pinned to the digests they were resolved to when hashing, so
+    /// that what was hashed is what is built even if a tag moves in the meantime. This also
+    /// includes the digests in the hash like `track_base_images`. Disabled by default.
+    pub fn pin_base_images(mut self, pin_base_images: bool) -> Self {
+        self.params.pin_base_images = pin_base_images;
+        self
+    }
+
+    /// Where the contents of the build and watch directories are read from, the filesystem by
+    /// default. `HashSource::Git` hashes only the files tracked by git.
+    pub fn hash_source(mut self, hash_source: HashSource) -> Self {
+        self.params.hash_source = hash_source;
+        self
+    }
+
+    /// What to do when the registry check fails for a reason other than a missing image.
+    pub fn registry_error_policy(mut self, policy: RegistryErrorPolicy) -> Self {
+        self.params.registry_error_policy = policy;
+        self
+    }
+
+    /// Where the version appended to the tags comes from, `./package.json` by default.
+    pub fn version_source(mut self, version_source: impl VersionSource + 'static) -> Self {
+        self.params.version_source = Arc::new(version_source);
+        self
+    }
+
+    /// Receives a `BuildEvent` for every step of the build.
+    pub fn on_event(mut self, handler: impl EventHandler + 'static) -> Self {
+        self.params.events = EventEmitter::new(Arc::new(handler));
+        self
+    }
+
+    /// Sends the events to an existing `EventEmitter`, e.g. one shared with other builds.
+    pub fn events(mut self, events: EventEmitter) -> Self {
+        self.params.events = events;
+        self
+    }
+
+    /// Validates and returns the parameters.
+    ///
+    /// # Returns
+    /// * `Ok(BuildDockerImageParams)` if the image name is set.
+    /// * `Err(DockemError::Validation)` otherwise.
+    pub fn build(self) -> Result<BuildDockerImageParams, DockemError> {
+        if self.params.image_name.trim().is_empty() {
+            return Err(DockemError::Validation(
+                "The image name is required, e.g. your-org/image-name.".to_string(),
+            ));
+        }
+        Ok(self.params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{
+        BuildDockerImageParams, DockemError, RegistryErrorPolicy, DEFAULT_HASH_CACHE_DIRECTORY,
+    };
+    use std::path::Path;
+
+    #[test]
+    fn test_builder_uses_cli_defaults() {
+        let params = BuildDockerImageParams::builder("my-org/backend")
+            .build()
+            .unwrap();
+
+        assert_eq!(params.directory, "./");
+        assert_eq!(params.dockerfile_path, "./Dockerfile");
+        assert_eq!(params.registry, "docker.io");
+        assert_eq!(params.registry_error_policy, RegistryErrorPolicy::Abort);
+        assert!(params.tag.is_empty());
+        assert!(params.watch_file.is_none());
+        assert!(params.watch_directory.is_none());
+        assert_eq!(
+            params.hash_cache_directory.as_deref(),
+            Some(Path::new(DEFAULT_HASH_CACHE_DIRECTORY))
+        );
+
+        let params = BuildDockerImageParams::builder("my-org/backend")
+            .hash_cache(false)
+            .build()
+            .unwrap();
+        assert!(params.hash_cache_directory.is_none());
+    }
+
+    #[test]
+    fn test_builder_collects_repeated_options() {
+        let params = BuildDockerImageParams::builder("my-org/backend")
+            .tag("dev")
+            .tag("alpha")
+            .watch_file("package-lock.json")
+            .watch_directory("libs/shared")
+            .watch_directory("libs/ui")
+            .build()
+            .unwrap();
+
+        assert_eq!(params.tag, vec!["dev", "alpha"]);
+        assert_eq!(
+            params.watch_file,
+            Some(vec!["package-lock.json".to_string()])
+        );
+        assert_eq!(
+            params.watch_directory,
+            Some(vec!["libs/shared".to_string(), "libs/ui".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_builder_requires_image_name() {
+        let result = BuildDockerImageParams::builder("  ").build();
+        assert!(matches!(result, Err(DockemError::Validation(_))));
+    }
+}
@@ -0,0 +1,107 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The phases of a dockem run, in the order they happen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildPhase {
+    Hash,
+    Version,
+    RegistryCheck,
+    Build,
+    Push,
+}
+
+/// Something that happened while building or reusing an image.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum BuildEvent {
+    /// A phase has started.
+    PhaseStarted { phase: BuildPhase },
+    /// A phase has finished successfully.
+    PhaseFinished {
+        phase: BuildPhase,
+        duration: Duration,
+    },
+    /// The image hash already exists on the registry, so the build is skipped.
+    CacheHit { image: String, digest: String },
+    /// The Docker build started executing step `step` of `total`, e.g. `RUN npm ci`.
+    BuildStep {
+        step: u32,
+        total: u32,
+        instruction: String,
+    },
+    /// Bytes uploaded so far while pushing `image`. When the Docker daemon pushes, `current` is
+    /// the progress of one layer and `total` its size if the daemon reported it, the daemon does
+    /// not name the layer in the stream. When dockem pushes a saved image itself, `current` is
+    /// the size of all layers pushed so far, including layers the registry already had, and
+    /// `total` is `None`.
+    PushProgress {
+        image: String,
+        current: u64,
+        total: Option<u64>,
+    },
+    /// The image was tagged and pushed to the registry.
+    TagPushed { image: String },
+    /// An existing image was copied to a new tag on the registry.
+    TagCopied { from: String, to: String },
+}
+
+/// Receives `BuildEvent`s while `build_docker_image` runs. Handlers are called inline, so they
+/// should return quickly. Any `Fn(&BuildEvent)` closure can be used as a handler.
+pub trait EventHandler: Send + Sync {
+    fn on_event(&self, event: &BuildEvent);
+}
+
+impl<F> EventHandler for F
+where
+    F: Fn(&BuildEvent) + Send + Sync,
+{
+    fn on_event(&self, event: &BuildEvent) {
+        self(event)
+    }
+}
+
+/// A cheap to clone handle that forwards events to an optional `EventHandler`.
+#[derive(Clone, Default)]
+pub struct EventEmitter {
+    handler: Option<Arc<dyn EventHandler>>,
+}
+
+impl EventEmitter {
+    /// Creates an emitter that forwards every event to the given handler.
+    pub fn new(handler: Arc<dyn EventHandler>) -> Self {
+        Self {
+            handler: Some(handler),
+        }
+    }
+
+    /// Sends the event to the handler, if there is one.
+    pub fn emit(&self, event: BuildEvent) {
+        if let Some(handler) = &self.handler {
+            handler.on_event(&event);
+        }
+    }
+
+    /// Emits `PhaseStarted` and returns the start time to pass to `phase_finished`.
+    pub fn phase_started(&self, phase: BuildPhase) -> Instant {
+        self.emit(BuildEvent::PhaseStarted { phase });
+        Instant::now()
+    }
+
+    /// Emits `PhaseFinished` with the time elapsed since `started`.
+    pub fn phase_finished(&self, phase: BuildPhase, started: Instant) {
+        self.emit(BuildEvent::PhaseFinished {
+            phase,
+            duration: started.elapsed(),
+        });
+    }
+}
+
+impl fmt::Debug for EventEmitter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventEmitter")
+            .field("has_handler", &self.handler.is_some())
+            .finish()
+    }
+}
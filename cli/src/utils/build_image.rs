@@ -1,17 +1,23 @@
-use crate::utils::{tar_build_context, BuildDockerImageParams, BuildLog};
+use crate::utils::{
+    parse_build_step, tar_build_context, BuildDockerImageParams, BuildEvent, BuildLog,
+    ResolvedBaseImage,
+};
 use anyhow::{anyhow, Result};
 use bollard::image::BuildImageOptions;
 use bollard::Docker;
 use futures_util::stream::StreamExt;
 use std::sync::{Arc, Mutex};
+use tracing::info;
 
 /// Builds a Docker image using the provided build context tarball.
-/// It will name the image local:imageHash.
+/// It will name the image local:imageHash and emit a `BuildEvent::BuildStep` for every step.
+/// The images in `params.cache_from` are used as a cache if the daemon has pulled them.
 ///
 /// # Arguments
 /// * `docker` - A connected Docker client.
 /// * `params` - Params from the user containing settings for the docker build.
 /// * `image_hash` - The hash of the image.
+/// * `base_images` - The resolved base images, see `tar_build_context`.
 /// * `build_log` - A shared, mutable reference to the build log.
 ///
 /// # Returns
@@ -20,22 +26,29 @@ pub async fn build_image(
     docker: &Docker,
     params: &BuildDockerImageParams,
     image_hash: &str,
+    base_images: &[ResolvedBaseImage],
     build_log: Arc<Mutex<BuildLog>>,
 ) -> Result<String> {
     // Create the build context tarball in a blocking task
     let build_context = {
         let build_log = Arc::clone(&build_log);
         let params_clone = Arc::from(params.clone());
+        let base_images = base_images.to_vec();
         tokio::task::spawn_blocking(move || {
             // Lock the Mutex to access the BuildLog
             let mut build_log = build_log.lock().unwrap();
-            tar_build_context(&params_clone, &mut build_log)
+            tar_build_context(&params_clone, &base_images, &mut build_log)
         })
         .await?? // Handle both the JoinError and the Result from tar_build_context
     };
 
     // Set up build options
     let local_tag = format!("local:{}", image_hash);
+    let cache_from: Vec<String> = params
+        .cache_from
+        .iter()
+        .filter_map(|cache| cache.reference(&params.registry, &params.image_name))
+        .collect();
     let build_options = BuildImageOptions {
         dockerfile: build_context
             .dockerfile_path
@@ -44,11 +57,12 @@ pub async fn build_image(
             .unwrap_or("Dockerfile"), // Use the returned Dockerfile path or fallback to "Dockerfile"
         t: &local_tag, // Tag the image with the provided name
         rm: true,      // Remove intermediate containers after a successful build
+        cachefrom: cache_from.iter().map(String::as_str).collect(),
         ..Default::default()
     };
 
     // Build the image
-    println!("Building image: {}", local_tag);
+    info!("Building image: {}", local_tag);
     let mut build_stream = docker.build_image(
         build_options,
         None,
@@ -59,8 +73,19 @@ pub async fn build_image(
     while let Some(output) = build_stream.next().await {
         match output {
             Ok(output) => {
+                // Podman can report a failed step in the error detail alone
+                if let Some(message) = output.error_detail.and_then(|detail| detail.message) {
+                    return Err(anyhow!("Build failed: {}", message));
+                }
                 if let Some(message) = output.stream {
-                    println!("{}", message);
+                    if let Some((step, total, instruction)) = parse_build_step(&message) {
+                        params.events.emit(BuildEvent::BuildStep {
+                            step,
+                            total,
+                            instruction,
+                        });
+                    }
+                    info!(target: "docker", "{}", message.trim_end());
                 }
             }
             Err(e) => return Err(anyhow!("Build failed: {}", e)),
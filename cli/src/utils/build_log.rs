@@ -1,16 +1,23 @@
+use crate::utils::{ManifestDiff, ResolvedBaseImage};
+use serde::Serialize;
+
 /// This struct is used to save the process of the build and any variables as well.
-/// It is used in testing to ensure that the expected outcomes are met.
-#[derive(Debug, Default, Clone)]
+/// It is used in testing to ensure that the expected outcomes are met, and written as the report
+/// of a build with a `BuildOutput`.
+#[derive(Debug, Default, Clone, Serialize)]
 pub struct BuildLog {
+    pub base_images: Vec<ResolvedBaseImage>,
     pub custom_dockerfile: bool,
     pub custom_host: bool,
+    #[serde(skip)]
     pub docker_password: Option<String>,
     pub docker_registry: Option<String>,
     pub docker_username: Option<String>,
     pub hash_exists: bool,
     pub hashed_image_name: String,
     pub image_hash: String,
-    pub local_tag: String,
+    pub local_tag: Option<String>,
+    pub manifest_diff: Option<ManifestDiff>,
     pub output_tags: Vec<String>,
     pub version: String,
 }
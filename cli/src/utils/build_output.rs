@@ -0,0 +1,179 @@
+use crate::utils::{BuildLog, DockerArchive, OciLayout};
+use anyhow::{anyhow, Context, Result};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The name of the build report in an OCI layout directory.
+const OCI_BUILD_REPORT_NAME: &str = "dockem-build.json";
+
+/// Where an image is written instead of being pushed to the registry, e.g. for air-gapped
+/// deliveries. The destination is also checked for the hashed image before building, so an
+/// OCI layout directory can act as the cache of a local build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildOutput {
+    /// A directory in the OCI image layout, which can hold many images.
+    Oci { dest: PathBuf },
+    /// A tar file that `docker load` reads, it only holds the last image written to it.
+    DockerArchive { dest: PathBuf },
+}
+
+impl BuildOutput {
+    /// Returns the directory or file the image is written to.
+    pub fn dest(&self) -> &Path {
+        match self {
+            BuildOutput::Oci { dest } | BuildOutput::DockerArchive { dest } => dest,
+        }
+    }
+
+    /// Returns where the build report is written, `dockem-build.json` inside an OCI layout or
+    /// e.g. `image.build.json` next to `image.tar`.
+    pub fn report_path(&self) -> PathBuf {
+        match self {
+            BuildOutput::Oci { dest } => dest.join(OCI_BUILD_REPORT_NAME),
+            BuildOutput::DockerArchive { dest } => dest.with_extension("build.json"),
+        }
+    }
+
+    /// Checks whether the image was already written to the destination.
+    ///
+    /// # Arguments
+    /// * `image_name` - The hashed image name.
+    ///
+    /// # Returns
+    /// * `Ok(true)` if the destination contains the image.
+    /// * `Err(anyhow::Error)` if the destination exists but cannot be read.
+    pub fn contains(&self, image_name: &str) -> Result<bool> {
+        match self {
+            BuildOutput::Oci { dest } => Ok(OciLayout::new(dest).find(image_name)?.is_some()),
+            BuildOutput::DockerArchive { dest } if dest.exists() => {
+                let archive = DockerArchive::open(dest)?;
+                Ok(archive.repo_tags.iter().any(|tag| tag == image_name))
+            }
+            BuildOutput::DockerArchive { .. } => Ok(false),
+        }
+    }
+
+    /// Writes an image to the destination under the given names.
+    ///
+    /// # Arguments
+    /// * `archive` - The built image, or `None` to tag the image the destination already
+    ///   contains under the first name.
+    /// * `image_names` - The hashed image name followed by the other names of the image.
+    ///
+    /// # Returns
+    /// * `Ok(())` if the image was written.
+    /// * `Err(anyhow::Error)` if the image cannot be read or written.
+    pub fn write(&self, archive: Option<&DockerArchive>, image_names: &[String]) -> Result<()> {
+        match self {
+            BuildOutput::Oci { dest } => {
+                let layout = OciLayout::new(dest);
+                let entry = match archive {
+                    Some(archive) => layout.add_image(archive)?,
+                    None => {
+                        let image_name = image_names
+                            .first()
+                            .ok_or_else(|| anyhow!("No image name to tag"))?;
+                        layout.find(image_name)?.ok_or_else(|| {
+                            anyhow!("{} does not contain {}", dest.display(), image_name)
+                        })?
+                    }
+                };
+                layout.tag(&entry, image_names)
+            }
+            BuildOutput::DockerArchive { dest } => match archive {
+                Some(archive) => archive.write_tagged(dest, image_names),
+                None => DockerArchive::open(dest)?.write_tagged(dest, image_names),
+            },
+        }
+    }
+
+    /// Writes the build log as JSON next to the image, without the registry password.
+    ///
+    /// # Arguments
+    /// * `build_log` - The log of the finished build.
+    ///
+    /// # Returns
+    /// * `Ok(PathBuf)` containing the path of the report.
+    /// * `Err(anyhow::Error)` if the report cannot be written.
+    pub fn write_report(&self, build_log: &BuildLog) -> Result<PathBuf> {
+        let path = self.report_path();
+        fs::write(&path, serde_json::to_vec_pretty(build_log)?)
+            .with_context(|| format!("Unable to write the build report {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+impl FromStr for BuildOutput {
+    type Err = String;
+
+    /// Parses `type=oci,dest=./out` or `type=docker-archive,dest=image.tar`, like the `--output`
+    /// of `docker buildx build`. `docker` is accepted for `docker-archive`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut kind = None;
+        let mut dest = None;
+        for field in value.split(',') {
+            match field.split_once('=') {
+                Some(("type", value)) => kind = Some(value),
+                Some(("dest", value)) if !value.is_empty() => dest = Some(PathBuf::from(value)),
+                _ => {
+                    return Err(format!(
+                        "Invalid output field '{}', expected type=oci|docker-archive,dest=<path>",
+                        field
+                    ))
+                }
+            }
+        }
+
+        match (kind, dest) {
+            (Some("oci"), Some(dest)) => Ok(BuildOutput::Oci { dest }),
+            (Some("docker-archive" | "docker"), Some(dest)) => {
+                Ok(BuildOutput::DockerArchive { dest })
+            }
+            _ => Err(format!(
+                "Invalid output '{}', expected type=oci|docker-archive,dest=<path>",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for BuildOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildOutput::Oci { dest } => write!(f, "type=oci,dest={}", dest.display()),
+            BuildOutput::DockerArchive { dest } => {
+                write!(f, "type=docker-archive,dest={}", dest.display())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::BuildOutput;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_outputs_are_parsed_like_buildx() {
+        let output: BuildOutput = "type=oci,dest=./out".parse().unwrap();
+        assert_eq!(
+            output,
+            BuildOutput::Oci {
+                dest: PathBuf::from("./out")
+            }
+        );
+        assert_eq!(
+            output.report_path(),
+            PathBuf::from("./out/dockem-build.json")
+        );
+
+        let output: BuildOutput = "dest=image.tar,type=docker-archive".parse().unwrap();
+        assert_eq!(output.to_string(), "type=docker-archive,dest=image.tar");
+        assert_eq!(output.report_path(), PathBuf::from("image.build.json"));
+
+        assert!("type=oci".parse::<BuildOutput>().is_err());
+        assert!("type=registry,dest=./out".parse::<BuildOutput>().is_err());
+    }
+}
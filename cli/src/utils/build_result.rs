@@ -0,0 +1,50 @@
+use crate::utils::{BuildLog, ManifestDiff};
+
+/// Whether the hashed image was built or already existed on the registry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildOutcome {
+    /// The hash already existed, the output tags were copied on the registry.
+    Reused,
+    /// The image was built and pushed. It was tagged locally as `local_tag`, which is `None` when
+    /// the backend pushed it straight to the registry like `BuildctlBuildBackend`.
+    Built { local_tag: Option<String> },
+}
+
+/// The result of a successful `build_docker_image` run.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct BuildResult {
+    pub outcome: BuildOutcome,
+    /// The content hash of the watched files, directories and Dockerfile.
+    pub image_hash: String,
+    /// The full image name tagged with the content hash.
+    pub hashed_image_name: String,
+    /// The version that was appended to the output tags.
+    pub version: String,
+    /// Every image name that was pushed or copied, excluding the hashed image name.
+    pub output_tags: Vec<String>,
+    /// The files that changed since the last build, if the image was built and the hash manifest
+    /// of the last build could be fetched.
+    pub changes: Option<ManifestDiff>,
+}
+
+impl From<BuildLog> for BuildResult {
+    fn from(build_log: BuildLog) -> Self {
+        let outcome = if build_log.hash_exists {
+            BuildOutcome::Reused
+        } else {
+            BuildOutcome::Built {
+                local_tag: build_log.local_tag,
+            }
+        };
+
+        BuildResult {
+            outcome,
+            image_hash: build_log.image_hash,
+            hashed_image_name: build_log.hashed_image_name,
+            version: build_log.version,
+            output_tags: build_log.output_tags,
+            changes: build_log.manifest_diff,
+        }
+    }
+}
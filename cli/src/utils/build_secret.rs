@@ -0,0 +1,129 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// A file that is mounted into `RUN --mount=type=secret,id=...` instructions, e.g. an `.npmrc`
+/// with a registry token. The contents of a secret are never hashed or sent with the build
+/// context, a secret inside a hashed directory is left out of the hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildSecret {
+    /// The ID the Dockerfile mounts the secret with.
+    pub id: String,
+    /// The file holding the secret.
+    pub source: PathBuf,
+}
+
+impl BuildSecret {
+    /// Creates a secret with the given ID from a file.
+    pub fn new(id: impl Into<String>, source: impl Into<PathBuf>) -> Self {
+        Self {
+            id: id.into(),
+            source: source.into(),
+        }
+    }
+
+    /// Returns the path of the secret relative to the given directory, or `None` if the secret
+    /// is not inside it. Both paths are resolved first, so they may be spelled differently.
+    ///
+    /// # Arguments
+    /// * `directory` - The directory that is hashed or sent as the build context.
+    ///
+    /// # Returns
+    /// * `Option<PathBuf>` containing the relative path of the secret.
+    pub fn relative_to(&self, directory: &Path) -> Option<PathBuf> {
+        let source = self.source.canonicalize().ok()?;
+        let directory = directory.canonicalize().ok()?;
+        source
+            .strip_prefix(directory)
+            .ok()
+            .map(Path::to_path_buf)
+            .filter(|relative| !relative.as_os_str().is_empty())
+    }
+}
+
+impl FromStr for BuildSecret {
+    type Err = String;
+
+    /// Parses `id=npmrc,src=~/.npmrc` like `docker build --secret` does. `source` can be used
+    /// instead of `src` and a leading `~` is expanded to the home directory.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut id = None;
+        let mut source = None;
+        for field in value.split(',') {
+            match field.split_once('=') {
+                Some(("id", value)) => id = Some(value.to_string()),
+                Some(("src" | "source", value)) => source = Some(expand_home(value)),
+                Some(("type", "file")) => {}
+                _ => {
+                    return Err(format!(
+                        "Invalid secret field '{}', expected id=<id>,src=<file>",
+                        field
+                    ))
+                }
+            }
+        }
+
+        match (id, source) {
+            (Some(id), Some(source)) if !id.is_empty() => Ok(BuildSecret { id, source }),
+            _ => Err(format!(
+                "Invalid secret '{}', expected id=<id>,src=<file>",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for BuildSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "id={},src={}", self.id, self.source.display())
+    }
+}
+
+/// Replaces a leading `~` with the home directory, as the shell does not expand it after `src=`.
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            PathBuf::from(home).join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::BuildSecret;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_secrets_are_parsed_like_docker_build() {
+        let secret: BuildSecret = "id=npmrc,src=./.npmrc".parse().unwrap();
+        assert_eq!(secret, BuildSecret::new("npmrc", "./.npmrc"));
+        assert_eq!(secret.to_string(), "id=npmrc,src=./.npmrc");
+
+        let home = std::env::var("HOME").unwrap();
+        let secret: BuildSecret = "type=file,id=token,source=~/token".parse().unwrap();
+        assert_eq!(secret.source, PathBuf::from(home).join("token"));
+
+        assert!("id=npmrc".parse::<BuildSecret>().is_err());
+        assert!("npmrc,src=./.npmrc".parse::<BuildSecret>().is_err());
+    }
+
+    #[test]
+    fn test_secrets_are_found_inside_a_directory() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let context = temp_dir.path().join("app");
+        fs::create_dir_all(context.join("config")).unwrap();
+        fs::write(context.join("config/.npmrc"), "//registry/:_authToken=abc").unwrap();
+        fs::write(temp_dir.path().join("token"), "abc").unwrap();
+
+        let inside = BuildSecret::new("npmrc", context.join("config/../config/.npmrc"));
+        assert_eq!(
+            inside.relative_to(&context),
+            Some(Path::new("config/.npmrc").to_path_buf())
+        );
+        let outside = BuildSecret::new("token", temp_dir.path().join("token"));
+        assert_eq!(outside.relative_to(&context), None);
+    }
+}
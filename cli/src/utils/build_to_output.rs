@@ -0,0 +1,140 @@
+use crate::utils::{
+    create_build_backend, output_image_names, save_docker_image_to_temporary_archive,
+    BuildDockerImageParams, BuildEvent, BuildLog, BuildOutput, BuildPhase, BuildResult, BuiltImage,
+    DockemError, DockerArchive, ResolvedBaseImage,
+};
+use anyhow::{anyhow, Context, Result};
+use std::sync::{Arc, Mutex};
+use tempfile::TempPath;
+use tokio::task;
+use tracing::{info, info_span, Instrument};
+
+/// Builds an image into a `BuildOutput` instead of a registry. The destination takes the place of
+/// the registry: it is checked for the hashed image, an image that was already written is only
+/// tagged again, and the registry is never contacted. The build log is written next to the image.
+///
+/// # Arguments
+/// * `params` - The build parameters, with the empty tags already removed.
+/// * `output` - Where the image is written.
+/// * `version` - The version appended to the tags.
+/// * `base_images` - The resolved base images the image was hashed with.
+/// * `build_log` - The build log with the hash and the hashed image name filled in.
+///
+/// # Returns
+/// * `BuildResult` describing the hash, version and tags of the image.
+/// * `DockemError` describing which step of the process failed.
+pub async fn build_to_output(
+    params: &BuildDockerImageParams,
+    output: &BuildOutput,
+    version: &str,
+    base_images: &[ResolvedBaseImage],
+    mut build_log: BuildLog,
+) -> Result<BuildResult, DockemError> {
+    let events = &params.events;
+    let image_name = build_log.hashed_image_name.clone();
+
+    // Check the destination instead of the registry
+    let started = events.phase_started(BuildPhase::RegistryCheck);
+    let image_exists = task::spawn_blocking({
+        let output = output.clone();
+        let image_name = image_name.clone();
+        move || output.contains(&image_name)
+    })
+    .await
+    .context("Failed to check the output")
+    .and_then(|result| result)
+    .map_err(DockemError::Build)?;
+    events.phase_finished(BuildPhase::RegistryCheck, started);
+    build_log.hash_exists = image_exists;
+
+    let archive_path = if image_exists {
+        info!(
+            "Image {} already exists in {}. Adding tags...",
+            image_name,
+            output.dest().display()
+        );
+        events.emit(BuildEvent::CacheHit {
+            image: image_name.clone(),
+            digest: String::new(),
+        });
+        None
+    } else {
+        info!(
+            "Image {} does not exist in {}. Building...",
+            image_name,
+            output.dest().display()
+        );
+        let started = events.phase_started(BuildPhase::Build);
+        let archive_path = async {
+            let (backend, docker) = create_build_backend(params).await?;
+            info!("Building with the {} backend", backend.name());
+            let built_image = backend
+                .build(
+                    params,
+                    &build_log.image_hash,
+                    &image_name,
+                    base_images,
+                    Arc::new(Mutex::new(build_log.clone())),
+                )
+                .await
+                .map_err(DockemError::Build)?;
+
+            match (built_image, docker) {
+                (BuiltImage::Archive(path), _) => {
+                    TempPath::try_from_path(path).map_err(|error| DockemError::Build(error.into()))
+                }
+                (BuiltImage::Local(local_tag), Some((docker_client, _))) => {
+                    build_log.local_tag = Some(local_tag.clone());
+                    save_docker_image_to_temporary_archive(
+                        &docker_client,
+                        &local_tag,
+                        &build_log.image_hash,
+                    )
+                    .await
+                    .map_err(DockemError::Build)
+                }
+                (built_image, _) => Err(DockemError::Build(anyhow!(
+                    "The {} backend left the image as {:?} instead of writing it to an archive",
+                    backend.name(),
+                    built_image
+                ))),
+            }
+        }
+        .instrument(info_span!("build", image = %image_name))
+        .await?;
+        events.phase_finished(BuildPhase::Build, started);
+        Some(archive_path)
+    };
+
+    // Write the image and the report where the registry push would happen
+    let started = events.phase_started(BuildPhase::Push);
+    let mut image_names = vec![image_name.clone()];
+    image_names.extend(output_image_names(params, version));
+    build_log.output_tags = image_names[1..].to_vec();
+    let report = task::spawn_blocking({
+        let output = output.clone();
+        let build_log = build_log.clone();
+        move || -> Result<_> {
+            let archive = archive_path
+                .as_ref()
+                .map(|path| DockerArchive::open(path.to_path_buf()))
+                .transpose()?;
+            output.write(archive.as_ref(), &image_names)?;
+            output.write_report(&build_log)
+        }
+    })
+    .instrument(info_span!("output", image = %image_name))
+    .await
+    .context("Failed to write the output")
+    .and_then(|result| result)
+    .map_err(DockemError::Push)?;
+    events.phase_finished(BuildPhase::Push, started);
+    info!(
+        "Image {} written to {}, the build report to {}",
+        image_name,
+        output.dest().display(),
+        report.display()
+    );
+
+    Ok(BuildResult::from(build_log))
+}
@@ -0,0 +1,472 @@
+use crate::utils::{
+    parse_build_step, pin_base_images, temporary_archive, BuildBackend, BuildDockerImageParams,
+    BuildEvent, BuildLog, BuiltImage, EventEmitter, ResolvedBaseImage,
+};
+use anyhow::{anyhow, Context, Result};
+use futures_util::future::{BoxFuture, FutureExt};
+use openssl::base64;
+use std::collections::VecDeque;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use tracing::{debug, info};
+
+/// How many lines of the `buildctl` output are kept for the error message of a failed build.
+const ERROR_CONTEXT_LINES: usize = 20;
+
+/// Builds with `buildctl` against a BuildKit daemon and pushes the image straight to the
+/// registry, without going through a Docker daemon's image store. With a `BuildOutput` the image is
+/// written to an archive instead. `buildctl` must be installed, it reads the registry login from
+/// the Docker config like `docker push` does.
+pub struct BuildctlBuildBackend {
+    buildkit_host: Option<String>,
+}
+
+impl BuildctlBuildBackend {
+    /// Creates the backend for the given BuildKit daemon, e.g. `tcp://buildkitd:1234`. Without
+    /// one, `buildctl` uses `BUILDKIT_HOST` or its default socket.
+    pub fn new(buildkit_host: Option<String>) -> Self {
+        Self { buildkit_host }
+    }
+
+    /// Returns the arguments `buildctl` is run with.
+    ///
+    /// # Arguments
+    /// * `params` - The build parameters naming the build directory and registry.
+    /// * `image_name` - The hashed image name to push to.
+    /// * `dockerfile` - The Dockerfile to build, which may be a pinned copy of the original.
+    /// * `archive` - Where to write the image as a `docker save` archive instead of pushing it.
+    ///
+    /// # Returns
+    /// * `Vec<String>` containing the arguments, without the `buildctl` program itself.
+    pub fn arguments(
+        &self,
+        params: &BuildDockerImageParams,
+        image_name: &str,
+        dockerfile: &Path,
+        archive: Option<&Path>,
+    ) -> Vec<String> {
+        let mut arguments = Vec::new();
+        if let Some(host) = &self.buildkit_host {
+            arguments.extend(["--addr".to_string(), host.clone()]);
+        }
+
+        let dockerfile_directory = dockerfile
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let dockerfile_name = dockerfile
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "Dockerfile".to_string());
+        let insecure = |reference: &str| {
+            let registry = reference.split('/').next().unwrap_or_default();
+            if params
+                .insecure_registries
+                .iter()
+                .any(|insecure| insecure == registry)
+            {
+                ",registry.insecure=true"
+            } else {
+                ""
+            }
+        };
+        let output = match archive {
+            Some(archive) => format!("type=docker,name={},dest={}", image_name, archive.display()),
+            None => format!(
+                "type=image,name={},push=true{}",
+                image_name,
+                insecure(image_name)
+            ),
+        };
+
+        arguments.extend([
+            "build".to_string(),
+            "--frontend".to_string(),
+            "dockerfile.v0".to_string(),
+            "--local".to_string(),
+            format!("context={}", params.directory),
+            "--local".to_string(),
+            format!("dockerfile={}", dockerfile_directory.display()),
+            "--opt".to_string(),
+            format!("filename={}", dockerfile_name),
+            "--output".to_string(),
+            output,
+        ]);
+        for cache in &params.cache_from {
+            if let Some(reference) = cache.reference(&params.registry, &params.image_name) {
+                arguments.extend([
+                    "--import-cache".to_string(),
+                    format!("type=registry,ref={}{}", reference, insecure(&reference)),
+                ]);
+            }
+        }
+        for cache in &params.cache_to {
+            // mode=max also exports the layers of intermediate stages
+            let export = match cache.reference(&params.registry, &params.image_name) {
+                Some(reference) => format!(
+                    "type=registry,ref={},mode=max{}",
+                    reference,
+                    insecure(&reference)
+                ),
+                None => "type=inline".to_string(),
+            };
+            arguments.extend(["--export-cache".to_string(), export]);
+        }
+        for secret in &params.secrets {
+            arguments.extend(["--secret".to_string(), secret.to_string()]);
+        }
+        for ssh in &params.ssh {
+            arguments.extend(["--ssh".to_string(), ssh.clone()]);
+        }
+        arguments.extend(["--progress".to_string(), "plain".to_string()]);
+        arguments
+    }
+}
+
+impl BuildBackend for BuildctlBuildBackend {
+    fn name(&self) -> &'static str {
+        "buildctl"
+    }
+
+    fn build<'a>(
+        &'a self,
+        params: &'a BuildDockerImageParams,
+        image_hash: &'a str,
+        image_name: &'a str,
+        base_images: &'a [ResolvedBaseImage],
+        _build_log: Arc<Mutex<BuildLog>>,
+    ) -> BoxFuture<'a, Result<BuiltImage>> {
+        async move {
+            // Everything written for this build is kept out of the build context, in a directory
+            // only this user can read that is unique to this run
+            let scratch = tempfile::Builder::new()
+                .prefix(&format!("dockem-{}-", image_hash))
+                .tempdir()
+                .context("Unable to create a scratch directory for the build")?;
+            let archive = params
+                .output
+                .as_ref()
+                .map(|_| temporary_archive(image_hash))
+                .transpose()?;
+
+            let result = async {
+                let dockerfile = scratch_dockerfile(params, base_images, scratch.path())?;
+                let docker_config = write_docker_config(
+                    params,
+                    user_docker_config_directory().as_deref(),
+                    scratch.path(),
+                )?;
+
+                let arguments = self.arguments(params, image_name, &dockerfile, archive.as_deref());
+                info!("Building image {} with buildctl", image_name);
+                debug!("Running buildctl {}", arguments.join(" "));
+                let events = params.events.clone();
+                tokio::task::spawn_blocking(move || {
+                    run_buildctl(&arguments, docker_config.as_deref(), &events)
+                })
+                .await?
+            }
+            .await;
+
+            let _ = scratch.close(); // Ignore errors during cleanup
+            result?;
+            // The archive is removed by the caller once it was read
+            Ok(match archive {
+                Some(archive) => BuiltImage::Archive(archive.keep()?),
+                None => BuiltImage::Pushed,
+            })
+        }
+        .boxed()
+    }
+}
+
+/// Returns the Dockerfile to build. With pinned base images or secrets inside the build context a
+/// copy is written to the scratch directory. Secrets are kept out of the context that is sent to
+/// BuildKit with a Dockerfile specific ignore file next to the copy, which extends the ignore file
+/// of the original Dockerfile or the `.dockerignore` of the build context.
+fn scratch_dockerfile(
+    params: &BuildDockerImageParams,
+    base_images: &[ResolvedBaseImage],
+    scratch: &Path,
+) -> Result<PathBuf> {
+    let secrets = params.secrets_in(&params.directory);
+    if !params.pin_base_images && secrets.is_empty() {
+        return Ok(PathBuf::from(&params.dockerfile_path));
+    }
+
+    let contents = fs::read_to_string(&params.dockerfile_path)?;
+    let dockerfile = scratch.join("Dockerfile");
+    if params.pin_base_images {
+        fs::write(&dockerfile, pin_base_images(&contents, base_images))?;
+    } else {
+        fs::write(&dockerfile, contents)?;
+    }
+
+    let ignore = [
+        PathBuf::from(format!("{}.dockerignore", params.dockerfile_path)),
+        Path::new(&params.directory).join(".dockerignore"),
+    ]
+    .iter()
+    .find_map(|path| fs::read_to_string(path).ok());
+    if ignore.is_some() || !secrets.is_empty() {
+        let mut ignore = ignore.unwrap_or_default();
+        for secret in &secrets {
+            debug!(
+                "Leaving the secret {} out of the build context",
+                secret.display()
+            );
+            ignore.push_str(&format!("\n{}", secret.display()));
+        }
+        fs::write(scratch.join("Dockerfile.dockerignore"), ignore)?;
+    }
+    Ok(dockerfile)
+}
+
+/// Returns the directory of the user's Docker config, `DOCKER_CONFIG` or `~/.docker`.
+fn user_docker_config_directory() -> Option<PathBuf> {
+    env::var_os("DOCKER_CONFIG")
+        .filter(|directory| !directory.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".docker")))
+}
+
+/// Writes a copy of the user's Docker config with the credentials from the parameters merged in,
+/// so that `buildctl` pushes with them while the other logins and credential helpers still work
+/// for pulling base images. The copy is only readable by the current user. Returns the directory
+/// to set as `DOCKER_CONFIG`, or `None` without credentials.
+fn write_docker_config(
+    params: &BuildDockerImageParams,
+    user_config: Option<&Path>,
+    scratch: &Path,
+) -> Result<Option<PathBuf>> {
+    let (Some(username), Some(password)) = (
+        params.docker_username.as_deref().filter(|u| !u.is_empty()),
+        params.docker_password.as_deref().filter(|p| !p.is_empty()),
+    ) else {
+        return Ok(None);
+    };
+
+    let directory = scratch.join("docker");
+    fs::create_dir(&directory)?;
+    let auth = base64::encode_block(format!("{}:{}", username, password).as_bytes());
+    // Docker Hub logins are stored under the legacy index address
+    let server = match params.registry.as_str() {
+        "docker.io" | "index.docker.io" => "https://index.docker.io/v1/",
+        registry => registry,
+    };
+    let mut config = match user_config.map(|directory| directory.join("config.json")) {
+        Some(path) => match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("Invalid Docker config {}", path.display()))?,
+            Err(error) if error.kind() == ErrorKind::NotFound => serde_json::json!({}),
+            Err(error) => {
+                return Err(error).with_context(|| format!("Unable to read {}", path.display()))
+            }
+        },
+        None => serde_json::json!({}),
+    };
+    if !config.is_object() {
+        return Err(anyhow!("The Docker config is not a JSON object"));
+    }
+    for key in ["auths", "credHelpers"] {
+        if !config[key].is_object() {
+            config[key] = serde_json::json!({});
+        }
+    }
+    config["auths"][server] = serde_json::json!({ "auth": auth });
+    // An empty helper makes this registry use the login in `auths` even when `credsStore` is set
+    config["credHelpers"][server] = serde_json::json!("");
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(directory.join("config.json"))?
+        .write_all(config.to_string().as_bytes())?;
+    Ok(Some(directory))
+}
+
+/// Runs `buildctl`, logging its output and emitting the build steps.
+fn run_buildctl(
+    arguments: &[String],
+    docker_config: Option<&Path>,
+    events: &EventEmitter,
+) -> Result<()> {
+    let mut command = Command::new("buildctl");
+    command
+        .args(arguments)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    if let Some(docker_config) = docker_config {
+        command.env("DOCKER_CONFIG", docker_config);
+    }
+
+    let mut child = command.spawn().map_err(|error| match error.kind() {
+        ErrorKind::NotFound => anyhow!(
+            "Unable to find buildctl, install BuildKit from https://github.com/moby/buildkit or use --build-backend=daemon"
+        ),
+        _ => anyhow!("Unable to run buildctl: {}", error),
+    })?;
+
+    // The plain progress output is written to stderr
+    let mut last_lines = VecDeque::with_capacity(ERROR_CONTEXT_LINES);
+    if let Some(stderr) = child.stderr.take() {
+        for line in BufReader::new(stderr).lines() {
+            let line = line?;
+            if let Some((step, total, instruction)) = parse_build_step(&line) {
+                events.emit(BuildEvent::BuildStep {
+                    step,
+                    total,
+                    instruction,
+                });
+            }
+            info!(target: "buildkit", "{}", line);
+            if last_lines.len() == ERROR_CONTEXT_LINES {
+                last_lines.pop_front();
+            }
+            last_lines.push_back(line);
+        }
+    }
+
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(anyhow!(
+            "buildctl exited with {}:\n{}",
+            status,
+            Vec::from(last_lines).join("\n")
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{scratch_dockerfile, write_docker_config};
+    use crate::utils::{BuildCache, BuildDockerImageParams, BuildSecret, BuildctlBuildBackend};
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_buildctl_pushes_the_hashed_image() {
+        let params = BuildDockerImageParams::builder("my-org/app")
+            .directory("./apps/app")
+            .registry("localhost:5000")
+            .insecure_registry("localhost:5000")
+            .cache_from(BuildCache::Registry(None))
+            .cache_to(BuildCache::Registry(None))
+            .cache_to(BuildCache::Inline)
+            .secret(BuildSecret::new("npmrc", "/home/ci/.npmrc"))
+            .ssh("default")
+            .build()
+            .unwrap();
+        let backend = BuildctlBuildBackend::new(Some("tcp://buildkitd:1234".to_string()));
+
+        let arguments = backend.arguments(
+            &params,
+            "localhost:5000/my-org/app:h2-abc",
+            Path::new("./apps/app/Dockerfile.prod"),
+            None,
+        );
+        assert_eq!(
+            arguments.join(" "),
+            "--addr tcp://buildkitd:1234 build --frontend dockerfile.v0 \
+             --local context=./apps/app --local dockerfile=./apps/app --opt filename=Dockerfile.prod \
+             --output type=image,name=localhost:5000/my-org/app:h2-abc,push=true,registry.insecure=true \
+             --import-cache type=registry,ref=localhost:5000/my-org/app:buildcache,registry.insecure=true \
+             --export-cache type=registry,ref=localhost:5000/my-org/app:buildcache,mode=max,registry.insecure=true \
+             --export-cache type=inline \
+             --secret id=npmrc,src=/home/ci/.npmrc --ssh default --progress plain"
+        );
+    }
+
+    #[test]
+    fn test_secrets_are_left_out_of_the_build_context() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let context = temp_dir.path().join("app");
+        let scratch = temp_dir.path().join("scratch");
+        fs::create_dir_all(&context).unwrap();
+        fs::create_dir_all(&scratch).unwrap();
+        fs::write(context.join("Dockerfile"), "FROM node:20\n").unwrap();
+        fs::write(context.join(".dockerignore"), "node_modules").unwrap();
+        fs::write(context.join(".npmrc"), "//registry/:_authToken=abc").unwrap();
+        let params = BuildDockerImageParams::builder("my-org/app")
+            .directory(context.to_str().unwrap())
+            .dockerfile_path(context.join("Dockerfile").to_str().unwrap())
+            .secret(BuildSecret::new("npmrc", context.join(".npmrc")))
+            .build()
+            .unwrap();
+
+        let dockerfile = scratch_dockerfile(&params, &[], &scratch).unwrap();
+        assert_eq!(dockerfile, scratch.join("Dockerfile"));
+        assert_eq!(
+            fs::read_to_string(scratch.join("Dockerfile.dockerignore")).unwrap(),
+            "node_modules\n.npmrc"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_docker_config_is_only_readable_by_the_user() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let scratch = TempDir::new().expect("Failed to create temp dir");
+        let params = BuildDockerImageParams::builder("my-org/app")
+            .registry("registry.example.com")
+            .credentials("ci", "secret")
+            .build()
+            .unwrap();
+
+        let directory = write_docker_config(&params, None, scratch.path())
+            .unwrap()
+            .expect("Expected a Docker config with credentials");
+        let metadata = fs::metadata(directory.join("config.json")).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_docker_config_keeps_the_users_other_logins() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let user_config = temp_dir.path().join("user");
+        let scratch = temp_dir.path().join("scratch");
+        fs::create_dir_all(&user_config).unwrap();
+        fs::create_dir_all(&scratch).unwrap();
+        fs::write(
+            user_config.join("config.json"),
+            r#"{
+                "auths": {"ghcr.io": {"auth": "Z2g6dG9rZW4="}, "registry.example.com": {"auth": "b2xkOm9sZA=="}},
+                "credHelpers": {"123.dkr.ecr.eu-west-1.amazonaws.com": "ecr-login"},
+                "credsStore": "desktop"
+            }"#,
+        )
+        .unwrap();
+        let params = BuildDockerImageParams::builder("my-org/app")
+            .registry("registry.example.com")
+            .credentials("ci", "secret")
+            .build()
+            .unwrap();
+
+        let directory = write_docker_config(&params, Some(&user_config), &scratch)
+            .unwrap()
+            .expect("Expected a Docker config with credentials");
+        let config: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(directory.join("config.json")).unwrap())
+                .unwrap();
+
+        assert_eq!(config["auths"]["ghcr.io"]["auth"], "Z2g6dG9rZW4=");
+        assert_eq!(
+            config["auths"]["registry.example.com"]["auth"],
+            "Y2k6c2VjcmV0"
+        );
+        assert_eq!(
+            config["credHelpers"]["123.dkr.ecr.eu-west-1.amazonaws.com"],
+            "ecr-login"
+        );
+        assert_eq!(config["credHelpers"]["registry.example.com"], "");
+        assert_eq!(config["credsStore"], "desktop");
+    }
+}
@@ -0,0 +1,27 @@
+use oci_client::{Client as RegistryClient, Reference};
+use tracing::debug;
+
+/// Checks if the repository of the reference already contains a blob with a HEAD request, so that
+/// it does not have to be uploaded again. If the registry cannot be asked the blob is treated as
+/// missing, the upload then reports the actual error.
+///
+/// # Arguments
+/// * `reference` - The image whose repository is checked.
+/// * `digest` - The digest of the blob, e.g. `sha256:…`.
+/// * `registry_client` - The authenticated OCI registry client.
+///
+/// # Returns
+/// * `bool` A true or false flag indicating whether the blob exists in the repository.
+pub async fn check_blob_head(
+    reference: &Reference,
+    digest: &str,
+    registry_client: &RegistryClient,
+) -> bool {
+    match registry_client.blob_exists(reference, digest).await {
+        Ok(exists) => exists,
+        Err(error) => {
+            debug!("Unable to check for the blob {}: {}", digest, error);
+            false
+        }
+    }
+}
@@ -1,46 +1,126 @@
+use oci_client::errors::{OciDistributionError, OciErrorCode};
 use oci_client::secrets::RegistryAuth;
 use oci_client::{Client as RegistryClient, Reference};
+use tracing::info;
 
-/// Checks if the registry contains an image with the tag specified. If the manifest check fails it
-/// will return false. Otherwise, it will return true to indicate that the image does exist with the
-/// tag given.
+/// The outcome of checking the registry for an image tag.
+#[derive(Debug)]
+pub enum ManifestStatus {
+    /// The tag exists on the registry and points at the manifest with this digest.
+    Exists { digest: String },
+    /// The registry answered and the tag (or the whole repository) does not exist.
+    NotFound,
+    /// The registry could not be asked, e.g. because of authentication, DNS or server errors.
+    Error(OciDistributionError),
+}
+
+/// Checks if the registry contains an image with the tag specified. A `HEAD` request is used so
+/// that only the manifest digest is transferred, the registry client falls back to a `GET` if the
+/// registry does not return the `Docker-Content-Digest` header.
 ///
 /// # Arguments
 /// * `tag` - The image tag to check for.
-/// * `reference` - An object containing details about the image repository and registry to perform
-/// the check in.
+/// * `reference` - An object containing details about the image repository and registry to
+///   perform the check in.
 /// * `registry_client` The authenticated OCI registry client to connect and check the registry with
 ///
 /// # Returns
-/// * `bool` A true or false flag indicating whether the tag already exists in the image repository.
+/// * `ManifestStatus` Whether the tag exists, does not exist or whether the check failed.
 ///
 pub async fn check_manifest_head(
     tag: &str,
-    reference: Reference,
+    reference: &Reference,
     registry_client: &RegistryClient,
-) -> bool {
-    println!("Checking for the image hash {} on the registry.", tag);
+) -> ManifestStatus {
+    info!("Checking for the image hash {} on the registry.", tag);
     // Use anonymous here because the client should already be authenticated.
-    return match registry_client
-        .pull_image_manifest(&reference, &RegistryAuth::Anonymous)
+    match registry_client
+        .fetch_manifest_digest(reference, &RegistryAuth::Anonymous)
         .await
     {
-        Ok(_manifest) => true,
-        Err(error) => {
-            eprintln!(
-                "The image hash {} does not exist on the registry or we were unable to pull it.",
-                tag
-            );
-            if error
-                .to_string()
-                .to_lowercase()
-                .contains("authentication failure")
-            {
-                println!("WARN: Unable to pull the details from the registry, please ensure you have the correct credentials.");
-                println!("WARN: The build will continue, but this should investigated.");
-            }
-            eprintln!("{}", error);
-            false
+        Ok(digest) => ManifestStatus::Exists { digest },
+        Err(error) => classify_manifest_error(error),
+    }
+}
+
+/// Splits registry errors into "the manifest is not there" and everything else.
+///
+/// # Arguments
+/// * `error` - The error returned by the registry client when fetching the manifest.
+///
+/// # Returns
+/// * `ManifestStatus::NotFound` if the registry reported an unknown manifest or repository,
+///   otherwise `ManifestStatus::Error` wrapping the original error.
+pub fn classify_manifest_error(error: OciDistributionError) -> ManifestStatus {
+    let not_found = match &error {
+        OciDistributionError::ImageManifestNotFoundError(_) => true,
+        OciDistributionError::RegistryError { envelope, .. } => {
+            !envelope.errors.is_empty()
+                && envelope.errors.iter().all(|error| {
+                    matches!(
+                        error.code,
+                        OciErrorCode::ManifestUnknown | OciErrorCode::NameUnknown
+                    )
+                })
         }
+        OciDistributionError::ServerError { code, .. } => *code == 404,
+        _ => false,
     };
+
+    if not_found {
+        ManifestStatus::NotFound
+    } else {
+        ManifestStatus::Error(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{classify_manifest_error, ManifestStatus};
+    use oci_client::errors::{OciDistributionError, OciEnvelope, OciError, OciErrorCode};
+
+    fn registry_error(code: OciErrorCode) -> OciDistributionError {
+        OciDistributionError::RegistryError {
+            envelope: OciEnvelope {
+                errors: vec![OciError {
+                    code,
+                    message: String::new(),
+                    detail: serde_json::Value::Null,
+                }],
+            },
+            url: "https://registry.example.com/v2/org/image/manifests/hash".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_manifest_unknown_is_not_found() {
+        let status = classify_manifest_error(registry_error(OciErrorCode::ManifestUnknown));
+        assert!(matches!(status, ManifestStatus::NotFound));
+
+        let status = classify_manifest_error(registry_error(OciErrorCode::NameUnknown));
+        assert!(matches!(status, ManifestStatus::NotFound));
+
+        let status = classify_manifest_error(OciDistributionError::ImageManifestNotFoundError(
+            "org/image:hash".to_string(),
+        ));
+        assert!(matches!(status, ManifestStatus::NotFound));
+    }
+
+    #[test]
+    fn test_other_errors_are_not_treated_as_missing() {
+        let status = classify_manifest_error(registry_error(OciErrorCode::Denied));
+        assert!(matches!(status, ManifestStatus::Error(_)));
+
+        let status = classify_manifest_error(OciDistributionError::UnauthorizedError {
+            url: "https://registry.example.com".to_string(),
+        });
+        assert!(matches!(status, ManifestStatus::Error(_)));
+
+        let status = classify_manifest_error(OciDistributionError::ServerError {
+            code: 500,
+            url: "https://registry.example.com".to_string(),
+            message: "internal error".to_string(),
+        });
+        assert!(matches!(status, ManifestStatus::Error(_)));
+    }
 }
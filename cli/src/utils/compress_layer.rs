@@ -0,0 +1,137 @@
+use anyhow::{Context, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use oci_client::manifest::IMAGE_LAYER_GZIP_MEDIA_TYPE;
+use openssl::sha::Sha256;
+use std::io::{self, BufWriter, Read, Write};
+use tempfile::TempPath;
+
+/// The media type of zstd compressed layers, which `docker save` keeps as they are with the
+/// containerd image store.
+pub const IMAGE_LAYER_ZSTD_MEDIA_TYPE: &str = "application/vnd.oci.image.layer.v1.tar+zstd";
+
+/// A compressed layer that was written to a temporary file, see `compress_layer_to_file`.
+#[derive(Debug)]
+pub struct CompressedLayer {
+    /// The file holding the compressed layer, which is removed when this is dropped.
+    pub path: TempPath,
+    /// The digest of the compressed layer, e.g. `sha256:…`.
+    pub digest: String,
+    /// The size of the compressed layer in bytes.
+    pub size: u64,
+    /// The media type of the compressed layer.
+    pub media_type: &'static str,
+}
+
+/// Streams a layer into a temporary file, gzipping it if `docker save` left it uncompressed, and
+/// computes its digest on the way. Only a small buffer of the layer is held in memory.
+///
+/// # Arguments
+/// * `reader` - Reads a layer from a `DockerArchive`.
+///
+/// # Returns
+/// * `Ok(CompressedLayer)` pointing at the compressed layer.
+/// * `Err(anyhow::Error)` if the layer could not be read, compressed or written.
+pub fn compress_layer_to_file(mut reader: impl Read) -> Result<CompressedLayer> {
+    let mut magic = Vec::with_capacity(4);
+    (&mut reader).take(4).read_to_end(&mut magic)?;
+    let mut reader = magic.as_slice().chain(reader);
+
+    let file = tempfile::Builder::new()
+        .prefix("dockem-layer-")
+        .tempfile()
+        .context("Unable to create a temporary file for the layer")?;
+    let mut writer = DigestWriter {
+        inner: BufWriter::new(file.as_file().try_clone()?),
+        sha256: Sha256::new(),
+        size: 0,
+    };
+    let media_type = match compressed_media_type(&magic) {
+        Some(media_type) => {
+            io::copy(&mut reader, &mut writer)?;
+            media_type
+        }
+        None => {
+            let mut encoder = GzEncoder::new(&mut writer, Compression::fast());
+            io::copy(&mut reader, &mut encoder)?;
+            encoder.finish()?;
+            IMAGE_LAYER_GZIP_MEDIA_TYPE
+        }
+    };
+    writer.flush()?;
+    let DigestWriter { sha256, size, .. } = writer;
+
+    let digest = sha256
+        .finish()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    Ok(CompressedLayer {
+        path: file.into_temp_path(),
+        digest: format!("sha256:{}", digest),
+        size,
+        media_type,
+    })
+}
+
+/// Returns the media type of a layer that is already compressed, from its first bytes.
+fn compressed_media_type(data: &[u8]) -> Option<&'static str> {
+    match data.get(..4) {
+        Some([0x1f, 0x8b, ..]) => Some(IMAGE_LAYER_GZIP_MEDIA_TYPE),
+        Some([0x28, 0xb5, 0x2f, 0xfd]) => Some(IMAGE_LAYER_ZSTD_MEDIA_TYPE),
+        _ => None,
+    }
+}
+
+/// Writes through to a file while hashing and counting what was written.
+struct DigestWriter<W: Write> {
+    inner: W,
+    sha256: Sha256,
+    size: u64,
+}
+
+impl<W: Write> Write for DigestWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.sha256.update(&buf[..written]);
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::compress_layer_to_file;
+    use flate2::read::GzDecoder;
+    use oci_client::client::ImageLayer;
+    use std::fs;
+    use std::io::Read;
+
+    #[test]
+    fn test_layers_are_streamed_to_a_file_with_their_digest() {
+        let layer = compress_layer_to_file(b"layer contents".as_slice()).unwrap();
+        assert_eq!(
+            layer.media_type,
+            "application/vnd.oci.image.layer.v1.tar+gzip"
+        );
+        let gzipped = fs::read(&layer.path).unwrap();
+        assert_eq!(layer.size, gzipped.len() as u64);
+        assert_eq!(
+            layer.digest,
+            ImageLayer::new(gzipped.clone(), String::new(), None).sha256_digest()
+        );
+        let mut decompressed = String::new();
+        GzDecoder::new(gzipped.as_slice())
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, "layer contents");
+
+        // Compressed layers are copied as they are, short ones included
+        let copied = compress_layer_to_file(gzipped.as_slice()).unwrap();
+        assert_eq!(copied.digest, layer.digest);
+    }
+}
@@ -0,0 +1,698 @@
+use crate::utils::{
+    auto_watch_paths, hash_directory, hash_directory_files, hash_file, hash_file_named,
+    hash_git_directory, hash_string, project_relative_path, resolve_project_root,
+    unwatched_copy_sources, BuildDockerImageParams, HashCache, HashManifest, HashScheme,
+    HashSource, ResolvedBaseImage,
+};
+use anyhow::{anyhow, Context, Result};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::{debug, warn};
+
+/// What an input to the image hash is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HashInputKind {
+    WatchFile,
+    WatchDirectory,
+    BuildDirectory,
+    Dockerfile,
+    BaseImage,
+}
+
+impl fmt::Display for HashInputKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            HashInputKind::WatchFile => "watch_file",
+            HashInputKind::WatchDirectory => "watch_directory",
+            HashInputKind::BuildDirectory => "build_directory",
+            HashInputKind::Dockerfile => "dockerfile",
+            HashInputKind::BaseImage => "base_image",
+        };
+        f.pad(kind)
+    }
+}
+
+impl HashInputKind {
+    /// A human readable name for error messages, e.g. `watch file`.
+    fn describe(&self) -> &'static str {
+        match self {
+            HashInputKind::WatchFile => "watch file",
+            HashInputKind::WatchDirectory => "watch directory",
+            HashInputKind::BuildDirectory => "build directory",
+            HashInputKind::Dockerfile => "Dockerfile",
+            HashInputKind::BaseImage => "base image",
+        }
+    }
+}
+
+/// A single file or directory that contributed to the image hash.
+#[derive(Debug, Clone, Serialize)]
+pub struct HashInput {
+    pub kind: HashInputKind,
+    pub path: String,
+    pub hash: String,
+}
+
+/// The image hash together with every input that contributed to it, in the order they were
+/// combined.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageHash {
+    pub hash: String,
+    pub scheme: HashScheme,
+    pub inputs: Vec<HashInput>,
+    /// The hash of every file inside the inputs, used to explain why a hash changed. It is empty
+    /// for `HashScheme::V1`.
+    #[serde(skip)]
+    pub manifest: HashManifest,
+}
+
+/// Computes the content hash used to tag the image. The watch files, watch directories, the build
+/// directory (unless it is ignored) and the Dockerfile are hashed and combined in that order, so
+/// this is the single source of truth for both `build_docker_image` and the `hash` command.
+/// Directories are hashed from `params.hash_source`, from the filesystem with the `HashCache` in
+/// `params.hash_cache_directory` if it is set, relative to the project root. Paths are hashed relative to the project root, so
+/// the hash is the same wherever the project is checked out and however the paths are spelled.
+/// With `params.auto_watch` the sources of the `COPY` and `ADD` instructions are watched instead
+/// of the build directory, see `auto_watch_paths`. With `HashScheme::V1` the hash of older
+/// releases is computed instead.
+///
+/// # Arguments
+/// * `params` - The build parameters that name the inputs to hash.
+///
+/// # Returns
+/// * `Ok(ImageHash)` containing the final hash, the hash of every input and of every file.
+/// * `Err(anyhow::Error)` naming the input that could not be hashed.
+pub fn compute_image_hash(params: &BuildDockerImageParams) -> Result<ImageHash> {
+    compute_image_hash_with_base_images(params, &[])
+}
+
+/// Computes the content hash like `compute_image_hash` and also includes the digests of the base
+/// images, which are combined after the Dockerfile. Resolve them with `resolve_base_images`.
+///
+/// # Arguments
+/// * `params` - The build parameters that name the inputs to hash.
+/// * `base_images` - The base images of the Dockerfile and their digests.
+///
+/// # Returns
+/// * `Ok(ImageHash)` containing the final hash, the hash of every input and of every file.
+/// * `Err(anyhow::Error)` naming the input that could not be hashed.
+pub fn compute_image_hash_with_base_images(
+    params: &BuildDockerImageParams,
+    base_images: &[ResolvedBaseImage],
+) -> Result<ImageHash> {
+    if params.auto_watch {
+        let params = with_auto_watch_paths(params)?;
+        return compute_image_hash_with_base_images(&params, base_images);
+    }
+    if params.ignore_build_directory {
+        warn_unwatched_copy_sources(params);
+    }
+
+    if params.hash_scheme == HashScheme::V1 {
+        if !base_images.is_empty() {
+            return Err(anyhow!("Tracking base images requires the hash scheme v2"));
+        }
+        return compute_v1_image_hash(params);
+    }
+
+    let mut inputs = Vec::new();
+    let mut manifest = HashManifest::default();
+    let mut hash_accumulator = String::new();
+    let root = resolve_project_root(params)?;
+    let cache = params
+        .hash_cache_directory
+        .as_ref()
+        .filter(|_| params.hash_source == HashSource::Filesystem)
+        .map(|directory| HashCache::load(root.join(directory)));
+
+    let watch_files = params.watch_file.as_deref().unwrap_or_default();
+    if !watch_files.is_empty() {
+        let file_inputs = hash_inputs(
+            &root,
+            watch_files,
+            HashInputKind::WatchFile,
+            |file, name| {
+                hash_file_named(file, name)
+                    .with_context(|| format!("Failed to hash the watch file '{}'", file))
+            },
+        )?;
+        hash_accumulator.push_str(&combine_hashes(&file_inputs));
+        manifest.files.extend(
+            file_inputs
+                .iter()
+                .map(|(name, input)| (name.clone(), input.hash.clone())),
+        );
+        inputs.extend(file_inputs.into_iter().map(|(_, input)| input));
+    }
+
+    let watch_directories = params.watch_directory.as_deref().unwrap_or_default();
+    if !watch_directories.is_empty() {
+        let directory_files = Mutex::new(Vec::new());
+        let directory_inputs = hash_inputs(
+            &root,
+            watch_directories,
+            HashInputKind::WatchDirectory,
+            |directory, name| {
+                if !Path::new(directory).is_dir() {
+                    return Err(anyhow!(
+                        "The watch directory '{}' is not a directory",
+                        directory
+                    ));
+                }
+                let (hash, files) =
+                    hash_directory_from_source(params, directory, name, cache.as_ref())
+                        .with_context(|| {
+                            format!("Failed to hash the watch directory '{}'", directory)
+                        })?;
+                directory_files
+                    .lock()
+                    .unwrap()
+                    .extend(manifest_entries(name, files));
+                Ok(hash)
+            },
+        )?;
+        hash_accumulator.push_str(&combine_hashes(&directory_inputs));
+        manifest.files.extend(directory_files.into_inner().unwrap());
+        inputs.extend(directory_inputs.into_iter().map(|(_, input)| input));
+    }
+
+    if !params.ignore_build_directory {
+        let (hash, files) = project_relative_path(&root, &params.directory)
+            .and_then(|name| {
+                let (hash, files) =
+                    hash_directory_from_source(params, &params.directory, &name, cache.as_ref())?;
+                Ok((hash, manifest_entries(&name, files).collect::<Vec<_>>()))
+            })
+            .with_context(|| {
+                format!("Failed to hash the build directory '{}'", params.directory)
+            })?;
+        hash_accumulator.push_str(&hash);
+        manifest.files.extend(files);
+        inputs.push(HashInput {
+            kind: HashInputKind::BuildDirectory,
+            path: params.directory.clone(),
+            hash,
+        });
+    }
+
+    let (name, hash) = project_relative_path(&root, &params.dockerfile_path)
+        .and_then(|name| {
+            let hash = hash_file_named(&params.dockerfile_path, &name)?;
+            Ok((name, hash))
+        })
+        .with_context(|| format!("Failed to hash the Dockerfile '{}'", params.dockerfile_path))?;
+    hash_accumulator.push_str(&hash);
+    manifest.files.insert(name, hash.clone());
+    inputs.push(HashInput {
+        kind: HashInputKind::Dockerfile,
+        path: params.dockerfile_path.clone(),
+        hash,
+    });
+
+    if !base_images.is_empty() {
+        let mut base_inputs: Vec<(String, HashInput)> = base_images
+            .iter()
+            .map(|base_image| {
+                let input = HashInput {
+                    kind: HashInputKind::BaseImage,
+                    path: base_image.image.clone(),
+                    hash: base_image.digest.clone(),
+                };
+                (format!("FROM {}", base_image.reference), input)
+            })
+            .collect();
+        base_inputs.sort_by(|first, second| first.0.cmp(&second.0));
+        hash_accumulator.push_str(&combine_hashes(&base_inputs));
+        manifest.files.extend(
+            base_inputs
+                .iter()
+                .map(|(name, input)| (name.clone(), input.hash.clone())),
+        );
+        inputs.extend(base_inputs.into_iter().map(|(_, input)| input));
+    }
+
+    if let Some(cache) = cache {
+        if let Err(error) = cache.save() {
+            warn!("Unable to save the hash cache: {}", error);
+        }
+    }
+
+    let hash = params
+        .hash_scheme
+        .image_hash(&hash_string(&hash_accumulator));
+    manifest.image_hash = hash.clone();
+    Ok(ImageHash {
+        hash,
+        scheme: params.hash_scheme,
+        inputs,
+        manifest,
+    })
+}
+
+/// Computes the hash the way releases before versioned hashing did, with the paths as they were
+/// passed and directories hashed by `MerkleTree`. The hash cache and git source are not supported.
+fn compute_v1_image_hash(params: &BuildDockerImageParams) -> Result<ImageHash> {
+    if params.hash_source != HashSource::Filesystem {
+        return Err(anyhow!(
+            "The hash source '{}' requires the hash scheme v2",
+            params.hash_source
+        ));
+    }
+    let mut hashed_directories = params.watch_directory.iter().flatten().collect::<Vec<_>>();
+    if !params.ignore_build_directory {
+        hashed_directories.push(&params.directory);
+    }
+    if let Some(secret) = params.secrets.iter().find(|secret| {
+        hashed_directories
+            .iter()
+            .any(|directory| secret.relative_to(Path::new(directory)).is_some())
+    }) {
+        return Err(anyhow!(
+            "The secret '{}' is inside a hashed directory, leaving it out of the hash requires the hash scheme v2",
+            secret.id
+        ));
+    }
+
+    let mut inputs = Vec::new();
+    let mut hash_accumulator = String::new();
+    let mut hash_sorted = |paths: &[String], kind: HashInputKind| -> Result<()> {
+        if paths.is_empty() {
+            return Ok(());
+        }
+        let mut sorted_paths = paths.to_vec();
+        sorted_paths.sort();
+        let hashed = sorted_paths
+            .into_par_iter()
+            .map(|path| {
+                let hash = match kind {
+                    HashInputKind::WatchDirectory => {
+                        hash_directory(&path).map_err(|error| anyhow!("{}", error))
+                    }
+                    _ => hash_file(&path).map_err(Into::into),
+                }
+                .with_context(|| format!("Failed to hash the {} '{}'", kind.describe(), path))?;
+                Ok((path.clone(), HashInput { kind, path, hash }))
+            })
+            .collect::<Result<Vec<(String, HashInput)>>>()?;
+        hash_accumulator.push_str(&combine_hashes(&hashed));
+        inputs.extend(hashed.into_iter().map(|(_, input)| input));
+        Ok(())
+    };
+    hash_sorted(
+        params.watch_file.as_deref().unwrap_or_default(),
+        HashInputKind::WatchFile,
+    )?;
+    hash_sorted(
+        params.watch_directory.as_deref().unwrap_or_default(),
+        HashInputKind::WatchDirectory,
+    )?;
+
+    let mut push = |kind: HashInputKind, path: &str, hash: String| {
+        hash_accumulator.push_str(&hash);
+        inputs.push(HashInput {
+            kind,
+            path: path.to_string(),
+            hash,
+        });
+    };
+    if !params.ignore_build_directory {
+        let hash = hash_directory(&params.directory).map_err(|error| {
+            anyhow!(
+                "Failed to hash the build directory '{}': {}",
+                params.directory,
+                error
+            )
+        })?;
+        push(HashInputKind::BuildDirectory, &params.directory, hash);
+    }
+    let hash = hash_file(&params.dockerfile_path)
+        .with_context(|| format!("Failed to hash the Dockerfile '{}'", params.dockerfile_path))?;
+    push(HashInputKind::Dockerfile, &params.dockerfile_path, hash);
+
+    let hash = HashScheme::V1.image_hash(&hash_string(&hash_accumulator));
+    Ok(ImageHash {
+        manifest: HashManifest {
+            image_hash: hash.clone(),
+            ..Default::default()
+        },
+        hash,
+        scheme: HashScheme::V1,
+        inputs,
+    })
+}
+
+/// Hashes a directory from the source chosen in the parameters, leaving out the secrets inside it.
+fn hash_directory_from_source(
+    params: &BuildDockerImageParams,
+    directory: &str,
+    name: &str,
+    cache: Option<&HashCache>,
+) -> Result<(String, Vec<(String, String)>)> {
+    let secrets = params.secrets_in(directory);
+    if !secrets.is_empty() {
+        debug!(
+            "Leaving the secrets {:?} in {} out of the hash",
+            secrets, name
+        );
+    }
+    match params.hash_source {
+        HashSource::Filesystem => hash_directory_files(directory, name, cache, &secrets),
+        HashSource::Git => hash_git_directory(directory, &secrets),
+    }
+}
+
+/// Prefixes the relative paths of the files in a directory with the directory's project path.
+fn manifest_entries(
+    name: &str,
+    files: Vec<(String, String)>,
+) -> impl Iterator<Item = (String, String)> + '_ {
+    files.into_iter().map(move |(relative, hash)| match name {
+        "." => (relative, hash),
+        _ => (format!("{}/{}", name, relative), hash),
+    })
+}
+
+/// Returns a copy of the parameters that watches the sources of the Dockerfile instead of the
+/// build directory. Sources that are already watched are not added again.
+fn with_auto_watch_paths(params: &BuildDockerImageParams) -> Result<BuildDockerImageParams> {
+    let root = resolve_project_root(params)?;
+    let (files, directories) = auto_watch_paths(params)?;
+    let mut params = params.clone();
+    params.auto_watch = false;
+    params.ignore_build_directory = true;
+
+    for (watched, derived) in [
+        (&mut params.watch_file, files),
+        (&mut params.watch_directory, directories),
+    ] {
+        let watched = watched.get_or_insert_with(Vec::new);
+        let watched_names = watched
+            .iter()
+            .filter_map(|path| project_relative_path(&root, path).ok())
+            .collect::<Vec<String>>();
+        for path in derived {
+            let name = project_relative_path(&root, &path)?;
+            if !watched_names.contains(&name) {
+                debug!("Watching '{}' from the Dockerfile", name);
+                watched.push(path);
+            }
+        }
+    }
+    Ok(params)
+}
+
+/// Warns about `COPY` and `ADD` sources that no watch covers while the build directory is not
+/// hashed, as changes to them would not trigger a rebuild.
+fn warn_unwatched_copy_sources(params: &BuildDockerImageParams) {
+    let unwatched =
+        resolve_project_root(params).and_then(|root| unwatched_copy_sources(params, &root));
+    match unwatched {
+        Ok(sources) => {
+            for source in sources {
+                warn!(
+                    "The {} source '{}' on line {} of the Dockerfile is not watched, changes to it will not trigger a rebuild. Add a watch for it or use --auto-watch",
+                    source.keyword, source.source, source.line
+                );
+            }
+        }
+        Err(error) => debug!(
+            "Unable to check the Dockerfile for unwatched sources: {:#}",
+            error
+        ),
+    }
+}
+
+/// Hashes the paths in parallel, sorted by their project path so that neither the order they were
+/// passed in nor how they were spelled matters. Every input is returned with its project path.
+fn hash_inputs<F>(
+    root: &Path,
+    paths: &[String],
+    kind: HashInputKind,
+    hash: F,
+) -> Result<Vec<(String, HashInput)>>
+where
+    F: Fn(&str, &str) -> Result<String> + Sync,
+{
+    let mut named_paths = paths
+        .iter()
+        .map(|path| {
+            let name = project_relative_path(root, path)
+                .with_context(|| format!("Failed to hash the {} '{}'", kind.describe(), path))?;
+            Ok((name, path.clone()))
+        })
+        .collect::<Result<Vec<(String, String)>>>()?;
+    named_paths.sort();
+
+    named_paths
+        .into_par_iter()
+        .map(|(name, path)| {
+            let input = HashInput {
+                kind,
+                hash: hash(&path, &name)?,
+                path,
+            };
+            Ok((name, input))
+        })
+        .collect()
+}
+
+/// Combines the hashes of several inputs of the same kind into one.
+fn combine_hashes(inputs: &[(String, HashInput)]) -> String {
+    let combined_hash_string: String = inputs
+        .iter()
+        .map(|(_, input)| input.hash.as_str())
+        .collect();
+    hash_string(&combined_hash_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{
+        compute_image_hash, compute_image_hash_with_base_images, hash_directory,
+        hash_directory_files, hash_file, hash_file_named, hash_string, hash_watch_directories,
+        hash_watch_files, BuildDockerImageParams, BuildSecret, HashInputKind, HashScheme,
+        ResolvedBaseImage, DEFAULT_HASH_CACHE_DIRECTORY,
+    };
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    /// Creates a small project in `root` and returns parameters that hash it.
+    fn checkout(root: &Path) -> BuildDockerImageParams {
+        fs::create_dir_all(root.join("app/src")).unwrap();
+        fs::create_dir_all(root.join("libs/shared")).unwrap();
+        fs::write(root.join("app/src/main.js"), "console.log('hi')").unwrap();
+        fs::write(root.join("app/Dockerfile"), "FROM node:20").unwrap();
+        fs::write(root.join("libs/shared/index.js"), "export {}").unwrap();
+        fs::write(root.join("package-lock.json"), "{}").unwrap();
+
+        let path = |relative: &str| root.join(relative).to_str().unwrap().to_string();
+        BuildDockerImageParams::builder("my-org/app")
+            .project_root(root)
+            .directory(path("app"))
+            .dockerfile_path(path("app/Dockerfile"))
+            .watch_file(path("package-lock.json"))
+            .watch_directory(path("libs/shared"))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_hash_matches_the_combined_input_hashes() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let root = temp_dir.path();
+        let params = checkout(root);
+
+        let image_hash = compute_image_hash(&params).unwrap();
+
+        let path = |relative: &str| root.join(relative).to_str().unwrap().to_string();
+        let expected = hash_string(
+            &[
+                hash_string(
+                    &hash_file_named(&path("package-lock.json"), "package-lock.json").unwrap(),
+                ),
+                hash_string(
+                    &hash_directory_files(&path("libs/shared"), "libs/shared", None, &[])
+                        .unwrap()
+                        .0,
+                ),
+                hash_directory_files(&path("app"), "app", None, &[])
+                    .unwrap()
+                    .0,
+                hash_file_named(&path("app/Dockerfile"), "app/Dockerfile").unwrap(),
+            ]
+            .concat(),
+        );
+        assert_eq!(image_hash.hash, format!("h2-{}", expected));
+        assert_eq!(image_hash.manifest.image_hash, image_hash.hash);
+        assert_eq!(
+            image_hash
+                .manifest
+                .files
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>(),
+            vec![
+                "app/Dockerfile",
+                "app/src/main.js",
+                "libs/shared/index.js",
+                "package-lock.json",
+            ]
+        );
+
+        let kinds: Vec<HashInputKind> = image_hash.inputs.iter().map(|input| input.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                HashInputKind::WatchFile,
+                HashInputKind::WatchDirectory,
+                HashInputKind::BuildDirectory,
+                HashInputKind::Dockerfile,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hash_cache_is_stored_in_the_project_root() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let root = temp_dir.path();
+
+        compute_image_hash(&checkout(root)).unwrap();
+
+        assert!(root.join(DEFAULT_HASH_CACHE_DIRECTORY).is_dir());
+    }
+
+    #[test]
+    fn test_hash_does_not_depend_on_the_checkout_location() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let first = compute_image_hash(&checkout(&temp_dir.path().join("runner-1"))).unwrap();
+
+        let mut params = checkout(&temp_dir.path().join("ci/runner-2/work"));
+        params.dockerfile_path = temp_dir
+            .path()
+            .join("ci/runner-2/work/app/../app/./Dockerfile")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let second = compute_image_hash(&params).unwrap();
+
+        assert_eq!(first.hash, second.hash);
+        assert_eq!(first.manifest.files, second.manifest.files);
+    }
+
+    #[test]
+    fn test_base_image_digests_change_the_hash() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let params = checkout(temp_dir.path());
+        let base_image = |digest: &str| ResolvedBaseImage {
+            image: "node:20".to_string(),
+            reference: "docker.io/library/node:20".to_string(),
+            digest: digest.to_string(),
+        };
+
+        let without = compute_image_hash(&params).unwrap();
+        let first =
+            compute_image_hash_with_base_images(&params, &[base_image("sha256:1")]).unwrap();
+        let second =
+            compute_image_hash_with_base_images(&params, &[base_image("sha256:2")]).unwrap();
+
+        assert_ne!(first.hash, without.hash);
+        assert_ne!(first.hash, second.hash);
+        assert_eq!(
+            first.manifest.files["FROM docker.io/library/node:20"],
+            "sha256:1"
+        );
+        assert_eq!(first.inputs.last().unwrap().kind, HashInputKind::BaseImage);
+    }
+
+    #[test]
+    fn test_v1_scheme_matches_older_releases() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let root = temp_dir.path();
+        let mut params = checkout(root);
+        params.hash_scheme = HashScheme::V1;
+
+        let image_hash = compute_image_hash(&params).unwrap();
+
+        let path = |relative: &str| root.join(relative).to_str().unwrap().to_string();
+        let expected = hash_string(
+            &[
+                hash_watch_files(&[path("package-lock.json")]).unwrap(),
+                hash_watch_directories(&[path("libs/shared")]).unwrap(),
+                hash_directory(&path("app")).unwrap(),
+                hash_file(&path("app/Dockerfile")).unwrap(),
+            ]
+            .concat(),
+        );
+        assert_eq!(image_hash.hash, expected);
+        assert_eq!(image_hash.inputs.len(), 4);
+        assert!(image_hash.manifest.files.is_empty());
+    }
+
+    #[test]
+    fn test_missing_watch_file_is_named_in_the_error() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let dockerfile = temp_dir.path().join("Dockerfile");
+        fs::write(&dockerfile, "FROM alpine").unwrap();
+
+        let params = BuildDockerImageParams::builder("my-org/app")
+            .dockerfile_path(dockerfile.to_str().unwrap())
+            .ignore_build_directory(true)
+            .watch_file("does-not-exist.json")
+            .build()
+            .unwrap();
+
+        let error = compute_image_hash(&params).unwrap_err();
+        assert!(error.to_string().contains("does-not-exist.json"));
+    }
+
+    #[test]
+    fn test_auto_watch_ignores_files_that_are_not_copied() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let root = temp_dir.path();
+        let mut params = checkout(root);
+        fs::write(
+            root.join("app/Dockerfile"),
+            "FROM node:20\nCOPY src ./src\n",
+        )
+        .unwrap();
+        fs::write(root.join("app/README.md"), "# App").unwrap();
+        params.auto_watch = true;
+
+        let image_hash = compute_image_hash(&params).unwrap();
+        assert!(!image_hash
+            .inputs
+            .iter()
+            .any(|input| input.kind == HashInputKind::BuildDirectory));
+
+        fs::write(root.join("app/README.md"), "# Changed").unwrap();
+        assert_eq!(compute_image_hash(&params).unwrap().hash, image_hash.hash);
+        fs::write(root.join("app/src/main.js"), "console.log('bye')").unwrap();
+        assert_ne!(compute_image_hash(&params).unwrap().hash, image_hash.hash);
+    }
+
+    #[test]
+    fn test_secrets_are_left_out_of_the_hash() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let root = temp_dir.path();
+        let mut params = checkout(root);
+        fs::write(root.join("app/.npmrc"), "//registry/:_authToken=abc").unwrap();
+        params.secrets = vec![BuildSecret::new("npmrc", root.join("app/.npmrc"))];
+
+        let image_hash = compute_image_hash(&params).unwrap();
+        assert!(!image_hash
+            .manifest
+            .files
+            .keys()
+            .any(|path| path.ends_with(".npmrc")));
+        fs::write(root.join("app/.npmrc"), "//registry/:_authToken=def").unwrap();
+        assert_eq!(compute_image_hash(&params).unwrap().hash, image_hash.hash);
+
+        params.hash_scheme = HashScheme::V1;
+        assert!(compute_image_hash(&params).is_err());
+    }
+}
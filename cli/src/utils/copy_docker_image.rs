@@ -3,6 +3,7 @@ use oci_client::client::Client as RegistryClient;
 use oci_client::secrets::RegistryAuth;
 use oci_client::Reference;
 use std::str::FromStr;
+use tracing::info;
 
 /// Copies a Docker image within the same registry or across registries.
 /// If the source and destination are in the same registry, it uses manifest re-tagging
@@ -31,7 +32,7 @@ pub async fn copy_docker_image(
 
     // Check if the source and destination are in the same registry
     if src_reference.registry() == dest_reference.registry() {
-        println!("Source and destination are in the same registry. Using manifest re-tagging...");
+        info!("Source and destination are in the same registry. Using manifest re-tagging...");
 
         // Fetch the manifest of the source image
         let (manifest, source_digest_hash) = registry_client
@@ -44,7 +45,7 @@ pub async fn copy_docker_image(
             registry_client.pull_manifest(&dest_reference, cred).await
         {
             if source_digest_hash == destination_digest_hash {
-                println!("Destination image already exists with the same digest. Skipping copy.");
+                info!("Destination image already exists with the same digest. Skipping copy.");
                 return Ok(());
             }
         }
@@ -55,9 +56,9 @@ pub async fn copy_docker_image(
             .await
             .context("Failed to push image manifest to destination")?;
 
-        println!("Image successfully copied within the same registry.");
+        info!("Image successfully copied within the same registry.");
     } else {
-        println!("Source and destination are in different registries. Performing standard pull and push...");
+        info!("Source and destination are in different registries. Performing standard pull and push...");
 
         // Define accepted media types for pulling the image
         let accepted_media_types = vec![
@@ -70,7 +71,7 @@ pub async fn copy_docker_image(
             .pull(&src_reference, cred, accepted_media_types)
             .await
             .context("Failed to pull source image layers")?;
-        println!(
+        info!(
             "Image layers successfully pulled from source registry. {}",
             src_reference.registry()
         );
@@ -86,7 +87,7 @@ pub async fn copy_docker_image(
             )
             .await
             .context("Failed to push image layer to destination")?;
-        println!("Image layers successfully copied between the registries.");
+        info!("Image layers successfully copied between the registries.");
         // Push the manifest to the destination
         let (source_manifest, _) = registry_client
             .pull_manifest(&src_reference, cred)
@@ -97,7 +98,7 @@ pub async fn copy_docker_image(
             .await
             .context("Failed to push image manifest to destination")?;
 
-        println!("Image successfully copied across registries.");
+        info!("Image successfully copied across registries.");
     }
 
     Ok(())
@@ -1,11 +1,14 @@
 use crate::utils::{
-    copy_docker_image, generate_docker_image_name, BuildDockerImageParams, BuildLog,
+    copy_docker_image, output_image_names, BuildDockerImageParams, BuildEvent, BuildLog,
 };
 use anyhow::{Context, Result};
 use oci_client::client::Client as RegistryClient;
 use oci_client::secrets::RegistryAuth;
+use tracing::info;
 
-/// Copies an existing image tag to new tags or to `latest` or `main version` based on the flags.
+/// Copies an existing image tag to every tag from `output_image_names` with manifest re-tagging.
+/// This is used both for an image that was already built and after pushing a new image.
+/// A `BuildEvent::TagCopied` is emitted for every copied tag.
 ///
 /// # Arguments
 /// * `params` - Build parameters containing registry, image name, and tagging options.
@@ -26,13 +29,8 @@ pub async fn copy_existing_image_tag(
     cred: &RegistryAuth,
     build_log: &mut BuildLog,
 ) -> Result<()> {
-    // Iterate over tags specified in parameters
-    for tag in &params.tag {
-        let tag_version = format!("{}-{}", tag, version);
-        let target_image_name =
-            generate_docker_image_name(&params.registry, &params.image_name, &tag_version);
-        println!("Copying the image to the new tag: {}", target_image_name);
-
+    for target_image_name in output_image_names(params, version) {
+        info!("Copying the image to the new tag: {}", target_image_name);
         copy_docker_image(
             image_name_with_hash,
             &target_image_name,
@@ -42,81 +40,12 @@ pub async fn copy_existing_image_tag(
         .await
         .with_context(|| format!("Failed to copy image to tag: {}", target_image_name))?;
 
+        params.events.emit(BuildEvent::TagCopied {
+            from: image_name_with_hash.to_string(),
+            to: target_image_name.clone(),
+        });
         build_log.output_tags.push(target_image_name);
     }
 
-    // If no tags are specified and neither latest nor main version are selected
-    if params.tag.is_empty() && !params.latest && !params.main_version {
-        let main_version_image_name =
-            generate_docker_image_name(&params.registry, &params.image_name, version);
-        println!(
-            "WARN: No tags were specified and --latest flag not selected. Copying to main version: {}",
-            main_version_image_name
-        );
-
-        copy_docker_image(
-            image_name_with_hash,
-            &main_version_image_name,
-            registry_client,
-            cred,
-        )
-        .await
-        .with_context(|| {
-            format!(
-                "Failed to copy image to main version: {}",
-                main_version_image_name
-            )
-        })?;
-
-        build_log.output_tags.push(main_version_image_name);
-    }
-
-    // If the --latest flag is selected
-    if params.latest {
-        let latest_image_name =
-            generate_docker_image_name(&params.registry, &params.image_name, "latest");
-        println!(
-            "You have selected the --latest flag. Copying to latest tag: {}",
-            latest_image_name
-        );
-
-        copy_docker_image(
-            image_name_with_hash,
-            &latest_image_name,
-            registry_client,
-            cred,
-        )
-        .await
-        .with_context(|| format!("Failed to copy image to latest tag: {}", latest_image_name))?;
-
-        build_log.output_tags.push(latest_image_name);
-    }
-
-    // If the --main-version flag is selected
-    if params.main_version {
-        let main_version_image_name =
-            generate_docker_image_name(&params.registry, &params.image_name, version);
-        println!(
-            "You have selected the --main-version flag. Copying to main version: {}",
-            main_version_image_name
-        );
-
-        copy_docker_image(
-            image_name_with_hash,
-            &main_version_image_name,
-            registry_client,
-            cred,
-        )
-        .await
-        .with_context(|| {
-            format!(
-                "Failed to copy image to main version: {}",
-                main_version_image_name
-            )
-        })?;
-
-        build_log.output_tags.push(main_version_image_name);
-    }
-
     Ok(())
 }
@@ -0,0 +1,47 @@
+use crate::utils::{
+    create_docker_client, BuildBackend, BuildBackendKind, BuildDockerImageParams,
+    BuildctlBuildBackend, DaemonBuildBackend, DockemError,
+};
+use bollard::auth::DockerCredentials;
+use bollard::Docker;
+use tracing::info;
+
+/// Creates the backend that `params.build_backend` selects. The daemon backend connects to the
+/// Docker daemon first, its client is returned as well so that the built image can be pushed or
+/// saved from the daemon.
+///
+/// # Arguments
+/// * `params` - The build parameters naming the backend, daemon and credentials.
+///
+/// # Returns
+/// * `Ok((Box<dyn BuildBackend>, Option<(Docker, DockerCredentials)>))` containing the backend
+///   and, for the daemon backend, the connected Docker client and its credentials.
+/// * `Err(DockemError)` if the Docker daemon cannot be reached.
+pub async fn create_build_backend(
+    params: &BuildDockerImageParams,
+) -> Result<(Box<dyn BuildBackend>, Option<(Docker, DockerCredentials)>), DockemError> {
+    match params.build_backend {
+        BuildBackendKind::Daemon => {
+            // Create Docker client
+            let (docker_client, docker_credentials) = create_docker_client(
+                params.docker_host.as_deref(),
+                params.docker_username.as_deref(),
+                params.docker_password.as_deref(),
+                &params.registry,
+            )
+            .await?;
+            info!("Docker client authenticated successfully.");
+            Ok((
+                Box::new(DaemonBuildBackend::new(
+                    docker_client.clone(),
+                    docker_credentials.clone(),
+                )),
+                Some((docker_client, docker_credentials)),
+            ))
+        }
+        BuildBackendKind::Buildctl => Ok((
+            Box::new(BuildctlBuildBackend::new(params.buildkit_host.clone())),
+            None,
+        )),
+    }
+}
@@ -1,11 +1,15 @@
 use super::docker_config_loader::DockerConfig;
+use crate::utils::{DockemError, DockerEndpoint};
 use bollard::auth::DockerCredentials;
 use bollard::Docker;
+use std::env;
+use tracing::{debug, info};
 
 /// Creates a Docker client, either with explicit username/password credentials
 /// or by loading authentication details from the Docker configuration file.
 ///
 /// # Parameters
+/// - `docker_host`: An explicit daemon address, otherwise it is resolved with `DockerEndpoint::resolve`.
 /// - `username`: An optional username to authenticate with the Docker registry.
 /// - `password`: An optional password to authenticate with the Docker registry.
 /// - `registry_name`: The name of the Docker registry (e.g., "docker.io" or a custom registry).
@@ -18,16 +22,27 @@ use bollard::Docker;
 ///     using a configuration file.
 ///
 /// # Errors
-/// - If authentication fails or if no valid credentials are found, an error is returned.
+/// - `DockemError::Build` if the Docker daemon cannot be connected to.
 ///
 /// This function will either create a Docker client using the provided credentials
 /// or attempt to load credentials from the Docker configuration file (`~/.docker/config.json`).
-/// If no credentials are provided, it tries to fetch them from the `auths` field in the Docker config.
+/// If no credentials are provided, it tries to fetch them from the `auths` field in the Docker config,
+/// and if there are none the daemon is given empty credentials so that Podman uses its own login.
 pub async fn create_docker_client(
+    docker_host: Option<&str>,
     username: Option<&str>,
     password: Option<&str>,
     registry_name: &str,
-) -> Result<(Docker, DockerCredentials), Box<dyn std::error::Error>> {
+) -> Result<(Docker, DockerCredentials), DockemError> {
+    let endpoint = DockerEndpoint::resolve(docker_host, |name| env::var(name).ok())
+        .map_err(DockemError::Build)?;
+    info!("Connecting to the Docker daemon at {}", endpoint);
+    let docker = endpoint.connect()?;
+
+    // Empty strings count as missing, the CLI passes them when no credentials are given
+    let username = username.filter(|username| !username.is_empty());
+    let password = password.filter(|password| !password.is_empty());
+
     // Check if both username and password are provided
     if let (Some(user), Some(pass)) = (username, password) {
         // If credentials are provided, create a Docker client with the specified auth
@@ -41,16 +56,17 @@ pub async fn create_docker_client(
             registrytoken: None,
         };
 
-        let docker = Docker::connect_with_socket_defaults()?;
         Ok((docker, auth))
     } else {
         // No credentials provided, so we load the Docker config file
-        let docker_config = DockerConfig::load(None)?;
-
-        // Attempt to get the auth config for the specified registry (or default registry)
-        let auth_config = docker_config
-            .get_auth_config_for_registry(&registry_name)
-            .or_else(|| docker_config.get_auth_config_for_registry("docker.io"));
+        let auth_config = match DockerConfig::load(None) {
+            // Only the login of the registry that is pushed to, another registry's would leak
+            Ok(docker_config) => docker_config.get_auth_config_for_registry(registry_name),
+            Err(error) => {
+                debug!("Unable to load the Docker config file: {:#}", error);
+                None
+            }
+        };
 
         if let Some(auth_config) = auth_config {
             let auth = DockerCredentials {
@@ -63,10 +79,14 @@ pub async fn create_docker_client(
                 registrytoken: None,
             };
 
-            let docker = Docker::connect_with_socket_defaults()?;
             Ok((docker, auth))
         } else {
-            Err("No valid authentication configuration found.".into())
+            // Without credentials Podman falls back to its own login, and Docker pushes anonymously
+            debug!(
+                "No credentials found for {}, pushing without credentials",
+                registry_name
+            );
+            Ok((docker, DockerCredentials::default()))
         }
     }
 }
@@ -1,14 +1,15 @@
 use crate::utils::build_log::BuildLog;
-use anyhow::Result;
-use oci_client::client::{Client, ClientConfig, ClientProtocol};
+use crate::utils::DockemError;
+use oci_client::client::Client;
 use oci_client::secrets::RegistryAuth;
 use oci_client::{Reference, RegistryOperation};
-use std::error;
 use std::str::FromStr;
+use tracing::{debug, error};
 
-/// Creates an OCI distribution client and authenticates with the specified registry.
+/// Authenticates an OCI distribution client with the specified registry.
 ///
 /// # Arguments
+/// * `client` - The client to authenticate, see `create_registry_client`.
 /// * `registry` - The registry URL. E.g <AWS_ACCOUNT_ID>.dkr.ecr.eu-west-1.amazonaws.com, docker.io
 /// * `username` - The username for authentication.
 /// * `password` - The password for authentication.
@@ -16,14 +17,15 @@ use std::str::FromStr;
 /// * `build_log` - A mutable reference to the `BuildLog` struct to record the build state.
 ///
 /// # Returns
-/// * `Result<(Client, Reference), Box<dyn error::Error>>` containing the initialized and authenticated client that can pull and push images or an error if it fails.
+/// * `Result<(Client, Reference), DockemError>` containing the initialized and authenticated client that can pull and push images or an error if it fails.
 pub async fn create_regclient_client(
+    client: Client,
     registry: &str,
     username: &str,
     password: &str,
     docker_image_name: &str,
     build_log: &mut BuildLog,
-) -> Result<(Client, Reference), Box<dyn error::Error>> {
+) -> Result<(Client, Reference), DockemError> {
     let mut custom_host = false;
     let default_dockerhub_registry_for_client = "docker.io";
 
@@ -56,41 +58,39 @@ pub async fn create_regclient_client(
         RegistryAuth::Anonymous
     };
 
-    let client_config = ClientConfig {
-        protocol: ClientProtocol::Https,
-        ..Default::default()
-    };
-
-    let client = Client::new(client_config);
-
     // Attempt authentication with the registry
     // Construct a reference to an image in the registry
-    print!("Creating registry client {} ", docker_image_name);
-    let reference = Reference::from_str(&docker_image_name)?;
+    debug!("Creating registry client {}", docker_image_name);
+    let reference = Reference::from_str(docker_image_name).map_err(|err| {
+        DockemError::Validation(format!(
+            "The image name '{}' is not a valid image reference: {}",
+            docker_image_name, err
+        ))
+    })?;
 
     // Authenticate to ensure the client is ready for use
     client
         .auth(&reference, &registry_auth, RegistryOperation::Pull)
         .await
         .map_err(|err| {
-            eprintln!(
-                "ERROR: Failed to authenticate with registry for pull operation: '{}': {}",
-                build_log.docker_registry.clone().unwrap().to_string(),
+            error!(
+                "Failed to authenticate with registry for pull operation: '{}': {}",
+                reference.registry(),
                 err
             );
-            err
+            DockemError::from_registry_error(err)
         })?;
 
     client
         .auth(&reference, &registry_auth, RegistryOperation::Push)
         .await
         .map_err(|err| {
-            eprintln!(
-                "ERROR: Failed to authenticate with registry for push operation: '{}': {}",
-                build_log.docker_registry.clone().unwrap().to_string(),
+            error!(
+                "Failed to authenticate with registry for push operation: '{}': {}",
+                reference.registry(),
                 err
             );
-            err
+            DockemError::from_registry_error(err)
         })?;
     Ok((client, reference))
 }
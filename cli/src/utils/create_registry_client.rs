@@ -0,0 +1,124 @@
+use crate::utils::{BuildDockerImageParams, DockemError};
+use oci_client::client::{Certificate, CertificateEncoding, Client, ClientConfig, ClientProtocol};
+use std::fs;
+
+const PEM_END: &str = "-----END CERTIFICATE-----";
+
+/// Creates an OCI distribution client that trusts the CA certificates in
+/// `params.registry_ca_file` in addition to the system roots, and talks plain HTTP to the
+/// registries in `params.insecure_registries`. Every other registry is reached over HTTPS.
+///
+/// # Arguments
+/// * `params` - The build parameters with the TLS settings.
+///
+/// # Returns
+/// * `Ok(Client)` containing the unauthenticated client.
+/// * `Err(DockemError::Validation)` if the CA file cannot be read or holds no valid certificate.
+pub fn create_registry_client(params: &BuildDockerImageParams) -> Result<Client, DockemError> {
+    let mut extra_root_certificates = Vec::new();
+    if let Some(ca_file) = &params.registry_ca_file {
+        let bundle = fs::read_to_string(ca_file).map_err(|error| {
+            DockemError::Validation(format!(
+                "Unable to read the registry CA file '{}': {}",
+                ca_file.display(),
+                error
+            ))
+        })?;
+        // The registry client only reads the first certificate of a PEM, so a bundle is split
+        extra_root_certificates = bundle
+            .split_inclusive(PEM_END)
+            .filter(|block| block.contains(PEM_END))
+            .map(|block| Certificate {
+                encoding: CertificateEncoding::Pem,
+                data: block.trim().as_bytes().to_vec(),
+            })
+            .collect();
+        if extra_root_certificates.is_empty() {
+            return Err(DockemError::Validation(format!(
+                "The registry CA file '{}' does not contain any PEM certificates",
+                ca_file.display()
+            )));
+        }
+    }
+
+    let protocol = if params.insecure_registries.is_empty() {
+        ClientProtocol::Https
+    } else {
+        ClientProtocol::HttpsExcept(params.insecure_registries.clone())
+    };
+
+    Client::try_from(ClientConfig {
+        protocol,
+        extra_root_certificates,
+        ..Default::default()
+    })
+    .map_err(|error| {
+        DockemError::Validation(format!(
+            "Unable to set up the registry client, check the registry CA file: {}",
+            error
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{create_registry_client, BuildDockerImageParams, DockemError};
+    use openssl::asn1::Asn1Time;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use openssl::x509::{X509NameBuilder, X509};
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn self_signed_certificate(name: &str) -> String {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut subject = X509NameBuilder::new().unwrap();
+        subject.append_entry_by_text("CN", name).unwrap();
+        let subject = subject.build();
+
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        builder.set_subject_name(&subject).unwrap();
+        builder.set_issuer_name(&subject).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+        String::from_utf8(builder.build().to_pem().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_ca_bundles_are_loaded() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let bundle = temp_dir.path().join("ca.pem");
+        fs::write(
+            &bundle,
+            format!(
+                "# Internal CAs\n{}{}",
+                self_signed_certificate("root-a"),
+                self_signed_certificate("root-b")
+            ),
+        )
+        .unwrap();
+        let empty = temp_dir.path().join("empty.pem");
+        fs::write(&empty, "not a certificate").unwrap();
+
+        let params = |ca_file: &std::path::Path| {
+            BuildDockerImageParams::builder("my-org/app")
+                .registry_ca_file(ca_file)
+                .insecure_registry("localhost:5000")
+                .build()
+                .unwrap()
+        };
+        assert!(create_registry_client(&params(&bundle)).is_ok());
+        assert!(matches!(
+            create_registry_client(&params(&empty)),
+            Err(DockemError::Validation(_))
+        ));
+    }
+}
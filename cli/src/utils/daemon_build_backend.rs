@@ -0,0 +1,74 @@
+use crate::utils::build_image::build_image;
+use crate::utils::{BuildBackend, BuildDockerImageParams, BuildLog, BuiltImage, ResolvedBaseImage};
+use anyhow::Result;
+use bollard::auth::DockerCredentials;
+use bollard::image::CreateImageOptions;
+use bollard::Docker;
+use futures_util::future::{BoxFuture, FutureExt};
+use futures_util::stream::TryStreamExt;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, info};
+
+/// Builds through the legacy `/build` endpoint of a Docker or Podman daemon. The image is left
+/// in the daemon's image store as `local:<hash>` to be tagged and pushed by the daemon.
+pub struct DaemonBuildBackend {
+    docker: Docker,
+    credentials: DockerCredentials,
+}
+
+impl DaemonBuildBackend {
+    /// Creates the backend for a connected Docker client and the credentials it pulls cache
+    /// images with, see `create_docker_client`.
+    pub fn new(docker: Docker, credentials: DockerCredentials) -> Self {
+        Self {
+            docker,
+            credentials,
+        }
+    }
+
+    /// Pulls the images in `params.cache_from`, as the daemon only uses local images as a cache.
+    /// A cache image that cannot be pulled, e.g. before the first build, is skipped.
+    async fn pull_cache_images(&self, params: &BuildDockerImageParams) {
+        for cache in &params.cache_from {
+            let Some(reference) = cache.reference(&params.registry, &params.image_name) else {
+                continue;
+            };
+            let options = CreateImageOptions {
+                from_image: reference.as_str(),
+                ..Default::default()
+            };
+            let pulled = self
+                .docker
+                .create_image(Some(options), None, Some(self.credentials.clone()))
+                .try_collect::<Vec<_>>()
+                .await;
+            match pulled {
+                Ok(_) => info!("Pulled the cache image {}", reference),
+                Err(error) => debug!("Unable to pull the cache image {}: {}", reference, error),
+            }
+        }
+    }
+}
+
+impl BuildBackend for DaemonBuildBackend {
+    fn name(&self) -> &'static str {
+        "daemon"
+    }
+
+    fn build<'a>(
+        &'a self,
+        params: &'a BuildDockerImageParams,
+        image_hash: &'a str,
+        _image_name: &'a str,
+        base_images: &'a [ResolvedBaseImage],
+        build_log: Arc<Mutex<BuildLog>>,
+    ) -> BoxFuture<'a, Result<BuiltImage>> {
+        async move {
+            self.pull_cache_images(params).await;
+            let local_tag =
+                build_image(&self.docker, params, image_hash, base_images, build_log).await?;
+            Ok(BuiltImage::Local(local_tag))
+        }
+        .boxed()
+    }
+}
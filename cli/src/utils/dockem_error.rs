@@ -0,0 +1,132 @@
+use oci_client::errors::{OciDistributionError, OciErrorCode};
+use thiserror::Error;
+
+/// The errors that can end a dockem run. Each variant maps to its own process exit code so that
+/// CI pipelines can tell, for example, a failed Docker build apart from an unreachable registry.
+///
+/// | Exit code | Variant        | Meaning                                                   |
+/// |-----------|----------------|-----------------------------------------------------------|
+/// | 0         |                | Success                                                   |
+/// | 2         | `Validation`   | Invalid arguments, missing files or directories           |
+/// | 3         | `Hash`         | The watched files, directories or Dockerfile could not be hashed |
+/// | 4         | `Version`      | The version file could not be read or parsed              |
+/// | 5         | `RegistryAuth` | The registry rejected the credentials                     |
+/// | 6         | `RegistryIo`   | The registry could not be reached or returned an error    |
+/// | 7         | `Build`        | The Docker daemon could not be reached or the build failed |
+/// | 8         | `Push`         | Tagging, pushing or copying the image failed              |
+/// | 9         | `Lint`         | `lint` found errors in the Dockerfile                     |
+///
+/// Exit code 2 is also what `clap` uses for usage errors, so all argument problems share a code.
+#[derive(Debug, Error)]
+pub enum DockemError {
+    #[error("{0}")]
+    Validation(String),
+
+    #[error("Failed to compute the image hash: {0:#}")]
+    Hash(anyhow::Error),
+
+    #[error("Failed to extract the version: {0:#}")]
+    Version(anyhow::Error),
+
+    #[error("Failed to authenticate with the registry: {0:#}")]
+    RegistryAuth(anyhow::Error),
+
+    #[error("Failed to communicate with the registry: {0:#}")]
+    RegistryIo(anyhow::Error),
+
+    #[error("Failed to build the image: {0:#}")]
+    Build(anyhow::Error),
+
+    #[error("Failed to push the image: {0:#}")]
+    Push(anyhow::Error),
+
+    #[error("{0}")]
+    Lint(String),
+}
+
+impl DockemError {
+    /// Returns the documented process exit code for this error.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            DockemError::Validation(_) => 2,
+            DockemError::Hash(_) => 3,
+            DockemError::Version(_) => 4,
+            DockemError::RegistryAuth(_) => 5,
+            DockemError::RegistryIo(_) => 6,
+            DockemError::Build(_) => 7,
+            DockemError::Push(_) => 8,
+            DockemError::Lint(_) => 9,
+        }
+    }
+
+    /// Sorts an error from the registry client into an authentication or a communication error.
+    ///
+    /// # Arguments
+    /// * `error` - The error returned by the OCI registry client.
+    ///
+    /// # Returns
+    /// * `DockemError::RegistryAuth` if the registry rejected the credentials, otherwise
+    ///   `DockemError::RegistryIo`.
+    pub fn from_registry_error(error: OciDistributionError) -> DockemError {
+        let is_auth_error = match &error {
+            OciDistributionError::AuthenticationFailure(_)
+            | OciDistributionError::UnauthorizedError { .. } => true,
+            OciDistributionError::RegistryError { envelope, .. } => {
+                envelope.errors.iter().any(|error| {
+                    matches!(
+                        error.code,
+                        OciErrorCode::Unauthorized | OciErrorCode::Denied
+                    )
+                })
+            }
+            _ => false,
+        };
+
+        if is_auth_error {
+            DockemError::RegistryAuth(error.into())
+        } else {
+            DockemError::RegistryIo(error.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::DockemError;
+    use anyhow::anyhow;
+    use oci_client::errors::OciDistributionError;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_exit_codes_are_distinct() {
+        let errors = [
+            DockemError::Validation(String::new()),
+            DockemError::Hash(anyhow!("")),
+            DockemError::Version(anyhow!("")),
+            DockemError::RegistryAuth(anyhow!("")),
+            DockemError::RegistryIo(anyhow!("")),
+            DockemError::Build(anyhow!("")),
+            DockemError::Push(anyhow!("")),
+            DockemError::Lint(String::new()),
+        ];
+
+        let codes: HashSet<u8> = errors.iter().map(|error| error.exit_code()).collect();
+        assert_eq!(codes.len(), errors.len());
+        assert!(!codes.contains(&0));
+    }
+
+    #[test]
+    fn test_registry_errors_are_classified() {
+        let error = DockemError::from_registry_error(OciDistributionError::UnauthorizedError {
+            url: "https://registry.example.com".to_string(),
+        });
+        assert!(matches!(error, DockemError::RegistryAuth(_)));
+
+        let error = DockemError::from_registry_error(OciDistributionError::ServerError {
+            code: 503,
+            url: "https://registry.example.com".to_string(),
+            message: "unavailable".to_string(),
+        });
+        assert!(matches!(error, DockemError::RegistryIo(_)));
+    }
+}
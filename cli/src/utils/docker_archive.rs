@@ -0,0 +1,260 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufWriter, Read};
+use std::path::{Component, Path, PathBuf};
+use tar::{Archive, Builder, EntryType, Header};
+
+/// The name of the file that lists the images in a `docker save` archive.
+const ARCHIVE_MANIFEST_NAME: &str = "manifest.json";
+
+/// An image in `manifest.json`, the paths are relative to the root of the archive.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct ArchiveManifestEntry {
+    config: String,
+    #[serde(default)]
+    repo_tags: Vec<String>,
+    layers: Vec<String>,
+}
+
+/// An image saved with `docker save` or `podman save`. Both the legacy layout and the OCI layout
+/// of newer Docker releases list the config and layers of the image in `manifest.json`.
+#[derive(Debug, Clone)]
+pub struct DockerArchive {
+    path: PathBuf,
+    /// The path of the image config in the archive.
+    pub config: String,
+    /// The image names `docker load` tags the image with.
+    pub repo_tags: Vec<String>,
+    /// The paths of the layers in the archive, from the base layer up.
+    pub layers: Vec<String>,
+}
+
+impl DockerArchive {
+    /// Opens an archive and reads the first image in its `manifest.json`.
+    ///
+    /// # Arguments
+    /// * `path` - The path of the tar file written by `docker save`.
+    ///
+    /// # Returns
+    /// * `Ok(DockerArchive)` describing the image in the archive.
+    /// * `Err(anyhow::Error)` if the archive cannot be read or has no image in it.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let manifest = read_entry(&path, ARCHIVE_MANIFEST_NAME, |reader| {
+            let mut manifest = Vec::new();
+            reader.read_to_end(&mut manifest)?;
+            Ok(manifest)
+        })?;
+        let entries: Vec<ArchiveManifestEntry> = serde_json::from_slice(&manifest)
+            .with_context(|| format!("Invalid {} in {}", ARCHIVE_MANIFEST_NAME, path.display()))?;
+        let entry = entries
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("The archive {} contains no image", path.display()))?;
+
+        Ok(Self {
+            path,
+            config: entry.config,
+            repo_tags: entry.repo_tags,
+            layers: entry.layers,
+        })
+    }
+
+    /// Reads a file from the archive, following symlinks inside the archive.
+    ///
+    /// # Arguments
+    /// * `name` - The path of the file in the archive, e.g. one of `layers`.
+    ///
+    /// # Returns
+    /// * `Ok(Vec<u8>)` containing the contents of the file.
+    /// * `Err(anyhow::Error)` if the file is not in the archive.
+    pub fn read(&self, name: &str) -> Result<Vec<u8>> {
+        self.read_with(name, |reader| {
+            let mut contents = Vec::new();
+            reader.read_to_end(&mut contents)?;
+            Ok(contents)
+        })
+    }
+
+    /// Streams a file from the archive to `read`, following symlinks inside the archive. Large
+    /// layers are read this way so that they are never held in memory as a whole.
+    ///
+    /// # Arguments
+    /// * `name` - The path of the file in the archive, e.g. one of `layers`.
+    /// * `read` - Reads the contents of the file.
+    ///
+    /// # Returns
+    /// * `Ok(T)` containing what `read` returned.
+    /// * `Err(anyhow::Error)` if the file is not in the archive or `read` failed.
+    pub fn read_with<T>(
+        &self,
+        name: &str,
+        read: impl FnOnce(&mut dyn Read) -> Result<T>,
+    ) -> Result<T> {
+        read_entry(&self.path, name, read)
+    }
+
+    /// Writes a copy of the archive that only lists this image, tagged with the given names. The
+    /// copy is written next to `path` first, so `path` may be the archive itself.
+    ///
+    /// # Arguments
+    /// * `path` - The file to write the archive to.
+    /// * `repo_tags` - The image names `docker load` tags the image with.
+    ///
+    /// # Returns
+    /// * `Ok(())` if the archive was written.
+    /// * `Err(anyhow::Error)` if the archive could not be read or written.
+    pub fn write_tagged(&self, path: &Path, repo_tags: &[String]) -> Result<()> {
+        let manifest = serde_json::to_vec(&[ArchiveManifestEntry {
+            config: self.config.clone(),
+            repo_tags: repo_tags.to_vec(),
+            layers: self.layers.clone(),
+        }])?;
+
+        let partial = path.with_extension("partial");
+        let file = File::create(&partial)
+            .with_context(|| format!("Unable to create {}", partial.display()))?;
+        let mut builder = Builder::new(BufWriter::new(file));
+        let mut header = Header::new_gnu();
+        header.set_size(manifest.len() as u64);
+        header.set_mode(0o644);
+        builder.append_data(&mut header, ARCHIVE_MANIFEST_NAME, manifest.as_slice())?;
+
+        let mut archive = Archive::new(File::open(&self.path)?);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let name = entry.path()?.into_owned();
+            // The repositories file of the legacy layout would tag the image with the old names
+            if [ARCHIVE_MANIFEST_NAME, "repositories"]
+                .iter()
+                .any(|skipped| normalize(&name) == Path::new(skipped))
+            {
+                continue;
+            }
+            let mut header = entry.header().clone();
+            match entry.link_name()? {
+                Some(target) if header.entry_type() == EntryType::Symlink => {
+                    let target = target.into_owned();
+                    builder.append_link(&mut header, &name, target)?
+                }
+                _ => builder.append_data(&mut header, &name, &mut entry)?,
+            }
+        }
+        builder.into_inner()?.into_inner()?.sync_all()?;
+        fs::rename(&partial, path)
+            .with_context(|| format!("Unable to write the archive {}", path.display()))
+    }
+}
+
+/// Finds a file in the archive, `docker save` links the legacy layer paths to the OCI blobs.
+fn read_entry<T>(
+    path: &Path,
+    name: &str,
+    read: impl FnOnce(&mut dyn Read) -> Result<T>,
+) -> Result<T> {
+    let mut name = normalize(Path::new(name));
+    // A chain of links longer than this is treated as a loop
+    for _ in 0..8 {
+        let file = File::open(path)
+            .with_context(|| format!("Unable to open the archive {}", path.display()))?;
+        let mut archive = Archive::new(file);
+        let mut link = None;
+        // Seeking skips over the layers that are not read
+        for entry in archive.entries_with_seek()? {
+            let mut entry = entry?;
+            if normalize(&entry.path()?) != name {
+                continue;
+            }
+            if entry.header().entry_type() == EntryType::Symlink {
+                let target = entry
+                    .link_name()?
+                    .ok_or_else(|| anyhow!("The link {} has no target", name.display()))?;
+                link = Some(normalize(
+                    &name.parent().unwrap_or(Path::new("")).join(target),
+                ));
+                break;
+            }
+            return read(&mut entry);
+        }
+        name = link.ok_or_else(|| {
+            anyhow!(
+                "The archive {} does not contain {}",
+                path.display(),
+                name.display()
+            )
+        })?;
+    }
+    Err(anyhow!(
+        "Too many links to {} in the archive",
+        name.display()
+    ))
+}
+
+/// Resolves `.` and `..` in a path inside the archive.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::Normal(part) => normalized.push(part),
+            _ => {}
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::DockerArchive;
+    use std::fs::File;
+    use tar::{Builder, EntryType, Header};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_layers_are_read_through_links() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let path = temp_dir.path().join("image.tar");
+        let mut builder = Builder::new(File::create(&path).unwrap());
+        let mut append = |name: &str, data: &[u8]| {
+            let mut header = Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, data).unwrap();
+        };
+        append(
+            "manifest.json",
+            br#"[{"Config":"blobs/sha256/cfg","RepoTags":["local:abc"],"Layers":["abc/layer.tar"]}]"#,
+        );
+        append("blobs/sha256/cfg", b"{}");
+        append("blobs/sha256/layer", b"layer contents");
+        let mut header = Header::new_gnu();
+        header.set_entry_type(EntryType::Symlink);
+        header.set_size(0);
+        header.set_mode(0o644);
+        builder
+            .append_link(&mut header, "abc/layer.tar", "../blobs/sha256/layer")
+            .unwrap();
+        builder.finish().unwrap();
+        drop(builder);
+
+        let archive = DockerArchive::open(&path).unwrap();
+        assert_eq!(archive.config, "blobs/sha256/cfg");
+        assert_eq!(archive.read(&archive.config).unwrap(), b"{}");
+        assert_eq!(archive.read(&archive.layers[0]).unwrap(), b"layer contents");
+        assert!(archive.read("missing").is_err());
+
+        let tags = vec![
+            "eu.reg.io/app:h2-abc".to_string(),
+            "eu.reg.io/app:v1".to_string(),
+        ];
+        archive.write_tagged(&path, &tags).unwrap();
+        let archive = DockerArchive::open(&path).unwrap();
+        assert_eq!(archive.repo_tags, tags);
+        assert_eq!(archive.read(&archive.layers[0]).unwrap(), b"layer contents");
+    }
+}
@@ -1,9 +1,10 @@
+use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::env;
-use std::error::Error;
 use std::fs;
 use std::path::Path;
+use tracing::debug;
 
 #[derive(Deserialize, Serialize, Debug, Default)]
 pub(crate) struct DockerConfig {
@@ -26,7 +27,7 @@ impl DockerConfig {
     /// # Returns
     ///
     /// Returns a result containing the loaded `DockerConfig`, or an error if the operation fails.
-    pub fn load(config_path: Option<String>) -> Result<DockerConfig, Box<dyn Error>> {
+    pub fn load(config_path: Option<String>) -> Result<DockerConfig> {
         let config_file_path = match config_path {
             Some(path) => Path::new(&path).to_path_buf(),
             None => {
@@ -35,7 +36,7 @@ impl DockerConfig {
                 match env_config_path_str {
                     Ok(path) => Path::new(&path).to_path_buf(),
                     Err(_) => {
-                        println!("DOCKER_CONFIG is not set, trying default path");
+                        debug!("DOCKER_CONFIG is not set, trying default path");
                         // Default to ~/.docker/config.json if no path is provided
                         let home_dir = env::var("HOME")?;
                         Path::new(&home_dir).join(".docker/config.json")
@@ -45,7 +46,7 @@ impl DockerConfig {
         };
 
         if !config_file_path.exists() {
-            return Err("Docker config file does not exist.".into());
+            return Err(anyhow!("Docker config file does not exist."));
         }
 
         let config_data = fs::read_to_string(config_file_path)?;
@@ -0,0 +1,344 @@
+use crate::utils::DockemError;
+use anyhow::{anyhow, Context, Result};
+use bollard::{Docker, API_DEFAULT_VERSION};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How long a request to the Docker daemon may take, the same default as bollard's.
+const DOCKER_TIMEOUT_SECONDS: u64 = 120;
+
+#[cfg(unix)]
+const DEFAULT_DOCKER_HOST: &str = "unix:///var/run/docker.sock";
+#[cfg(unix)]
+const ROOTFUL_PODMAN_SOCKET: &str = "/run/podman/podman.sock";
+#[cfg(windows)]
+const DEFAULT_DOCKER_HOST: &str = "npipe:////./pipe/docker_engine";
+
+/// Where the Docker daemon is reached and how, resolved the same way the `docker` CLI does it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DockerEndpoint {
+    /// The daemon address, e.g. `unix:///var/run/docker.sock` or `tcp://build-host:2376`.
+    pub host: String,
+    /// The directory with `ca.pem`, `cert.pem` and `key.pem` if the connection uses TLS.
+    pub tls_cert_path: Option<PathBuf>,
+    /// What the endpoint was taken from, e.g. `DOCKER_HOST` or `context ci-builder`.
+    pub source: String,
+}
+
+/// The parts of a context's `meta.json` that dockem needs.
+#[derive(Deserialize)]
+struct ContextMeta {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "Endpoints")]
+    endpoints: HashMap<String, ContextEndpoint>,
+}
+
+#[derive(Deserialize)]
+struct ContextEndpoint {
+    #[serde(rename = "Host")]
+    host: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct CliConfig {
+    #[serde(rename = "currentContext")]
+    current_context: Option<String>,
+}
+
+impl fmt::Display for DockerEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (from {})", self.host, self.source)?;
+        if self.tls_cert_path.is_some() {
+            write!(f, " with TLS")?;
+        }
+        Ok(())
+    }
+}
+
+impl DockerEndpoint {
+    /// Resolves the Docker daemon to connect to. The first of these that is set wins:
+    /// 1. The `docker_host` argument, i.e. the `--docker-host` flag.
+    /// 2. The `DOCKER_HOST` environment variable, with TLS if `DOCKER_TLS_VERIFY` is set and the
+    ///    certificates in `DOCKER_CERT_PATH`, or the Docker config directory.
+    /// 3. The context named by `DOCKER_CONTEXT`, or the `currentContext` in `config.json`.
+    /// 4. The default socket, `/var/run/docker.sock`. If it does not exist, the Podman socket in
+    ///    `$XDG_RUNTIME_DIR/podman/podman.sock` or `/run/podman/podman.sock` is used instead.
+    ///
+    /// # Arguments
+    /// * `docker_host` - An explicit daemon address, if one was given.
+    /// * `env` - Looks up an environment variable, e.g. `|name| std::env::var(name).ok()`.
+    ///
+    /// # Returns
+    /// * `Ok(DockerEndpoint)` containing the daemon address and TLS settings.
+    /// * `Err(anyhow::Error)` if the selected context does not exist or cannot be read.
+    pub fn resolve(
+        docker_host: Option<&str>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<DockerEndpoint> {
+        let env = |name: &str| env(name).filter(|value| !value.is_empty());
+        let config_directory = env("DOCKER_CONFIG")
+            .map(PathBuf::from)
+            .or_else(|| env("HOME").map(|home| Path::new(&home).join(".docker")));
+
+        let tls_from_env = || {
+            env("DOCKER_TLS_VERIFY").and_then(|_| {
+                env("DOCKER_CERT_PATH")
+                    .map(PathBuf::from)
+                    .or_else(|| config_directory.clone())
+            })
+        };
+
+        if let Some(host) = docker_host.filter(|host| !host.is_empty()) {
+            return Ok(DockerEndpoint {
+                host: host.to_string(),
+                tls_cert_path: tls_from_env(),
+                source: "--docker-host".to_string(),
+            });
+        }
+        if let Some(host) = env("DOCKER_HOST") {
+            return Ok(DockerEndpoint {
+                host,
+                tls_cert_path: tls_from_env(),
+                source: "DOCKER_HOST".to_string(),
+            });
+        }
+
+        if let Some(config_directory) = &config_directory {
+            let context = match env("DOCKER_CONTEXT") {
+                Some(context) => Some(context),
+                None => read_current_context(config_directory)?,
+            };
+            if let Some(context) = context.filter(|context| context != "default") {
+                return read_context(config_directory, &context);
+            }
+        }
+
+        // Rootless Podman serves a Docker compatible API, it is only used without a Docker socket
+        #[cfg(unix)]
+        if !Path::new(DEFAULT_DOCKER_HOST.trim_start_matches("unix://")).exists() {
+            let podman_sockets = env("XDG_RUNTIME_DIR")
+                .map(|runtime| Path::new(&runtime).join("podman/podman.sock"))
+                .into_iter()
+                .chain([PathBuf::from(ROOTFUL_PODMAN_SOCKET)]);
+            for socket in podman_sockets {
+                if socket.exists() {
+                    return Ok(DockerEndpoint {
+                        host: format!("unix://{}", socket.display()),
+                        tls_cert_path: None,
+                        source: "the Podman socket".to_string(),
+                    });
+                }
+            }
+        }
+
+        Ok(DockerEndpoint {
+            host: DEFAULT_DOCKER_HOST.to_string(),
+            tls_cert_path: None,
+            source: "the default socket".to_string(),
+        })
+    }
+
+    /// Connects to the daemon. Nothing is sent until the first request.
+    ///
+    /// # Returns
+    /// * `Ok(Docker)` containing the client.
+    /// * `Err(DockemError::Build)` if the address is not supported or the TLS certificates
+    ///   cannot be used.
+    pub fn connect(&self) -> Result<Docker, DockemError> {
+        let host = self.host.as_str();
+        let docker = match &self.tls_cert_path {
+            Some(cert_path) if host.starts_with("tcp://") || host.starts_with("https://") => {
+                connect_with_tls(host, cert_path)?
+            }
+            _ if host.starts_with("tcp://") || host.starts_with("http://") => {
+                Docker::connect_with_http(host, DOCKER_TIMEOUT_SECONDS, API_DEFAULT_VERSION)
+                    .map_err(|error| DockemError::Build(error.into()))?
+            }
+            #[cfg(unix)]
+            _ if host.starts_with("unix://") => {
+                Docker::connect_with_unix(host, DOCKER_TIMEOUT_SECONDS, API_DEFAULT_VERSION)
+                    .map_err(|error| DockemError::Build(error.into()))?
+            }
+            #[cfg(windows)]
+            _ if host.starts_with("npipe://") => {
+                Docker::connect_with_named_pipe(host, DOCKER_TIMEOUT_SECONDS, API_DEFAULT_VERSION)
+                    .map_err(|error| DockemError::Build(error.into()))?
+            }
+            _ => {
+                return Err(DockemError::Build(anyhow!(
+                    "The Docker host '{}' from {} is not supported, use a unix://, tcp:// or https:// address",
+                    host,
+                    self.source
+                )))
+            }
+        };
+        Ok(docker)
+    }
+}
+
+#[cfg(feature = "docker-tls")]
+fn connect_with_tls(host: &str, cert_path: &Path) -> Result<Docker, DockemError> {
+    Docker::connect_with_ssl(
+        host,
+        &cert_path.join("key.pem"),
+        &cert_path.join("cert.pem"),
+        &cert_path.join("ca.pem"),
+        DOCKER_TIMEOUT_SECONDS,
+        API_DEFAULT_VERSION,
+    )
+    .map_err(|error| {
+        DockemError::Build(anyhow!(
+            "Unable to use the TLS certificates in '{}': {}",
+            cert_path.display(),
+            error
+        ))
+    })
+}
+
+#[cfg(not(feature = "docker-tls"))]
+fn connect_with_tls(host: &str, _cert_path: &Path) -> Result<Docker, DockemError> {
+    Err(DockemError::Build(anyhow!(
+        "Connecting to the Docker host '{}' over TLS needs dockem-rs built with the `docker-tls` feature",
+        host
+    )))
+}
+
+fn read_current_context(config_directory: &Path) -> Result<Option<String>> {
+    let config_path = config_directory.join("config.json");
+    if !config_path.exists() {
+        return Ok(None);
+    }
+    let contents = fs::read_to_string(&config_path)
+        .with_context(|| format!("Unable to read {}", config_path.display()))?;
+    let config: CliConfig = serde_json::from_str(&contents).unwrap_or_default();
+    Ok(config.current_context)
+}
+
+/// Finds a context by name in `contexts/meta`, the directories there are named after the
+/// SHA-256 of the context name so they are searched instead.
+fn read_context(config_directory: &Path, name: &str) -> Result<DockerEndpoint> {
+    let contexts = config_directory.join("contexts");
+    let entries = fs::read_dir(contexts.join("meta")).into_iter().flatten();
+    for entry in entries.flatten() {
+        let Ok(contents) = fs::read_to_string(entry.path().join("meta.json")) else {
+            continue;
+        };
+        let meta: ContextMeta = serde_json::from_str(&contents).with_context(|| {
+            format!(
+                "Unable to parse the Docker context in {}",
+                entry.path().display()
+            )
+        })?;
+        if meta.name != name {
+            continue;
+        }
+
+        let host = meta
+            .endpoints
+            .get("docker")
+            .and_then(|endpoint| endpoint.host.clone())
+            .ok_or_else(|| anyhow!("The Docker context '{}' has no Docker endpoint", name))?;
+        let tls_cert_path = contexts.join("tls").join(entry.file_name()).join("docker");
+        return Ok(DockerEndpoint {
+            host,
+            tls_cert_path: tls_cert_path
+                .join("ca.pem")
+                .exists()
+                .then_some(tls_cert_path),
+            source: format!("context {}", name),
+        });
+    }
+
+    Err(anyhow!(
+        "The Docker context '{}' does not exist in {}",
+        name,
+        contexts.display()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::DockerEndpoint;
+    use std::collections::HashMap;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_endpoint_precedence() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let config = temp_dir.path();
+        let meta = config.join("contexts/meta/0a1b2c");
+        fs::create_dir_all(&meta).unwrap();
+        fs::create_dir_all(config.join("contexts/tls/0a1b2c/docker")).unwrap();
+        fs::write(config.join("contexts/tls/0a1b2c/docker/ca.pem"), "").unwrap();
+        fs::write(
+            meta.join("meta.json"),
+            r#"{"Name":"ci-builder","Metadata":{},"Endpoints":{"docker":{"Host":"tcp://builder:2376","SkipTLSVerify":false}}}"#,
+        )
+        .unwrap();
+        fs::write(
+            config.join("config.json"),
+            r#"{"auths":{},"currentContext":"ci-builder"}"#,
+        )
+        .unwrap();
+
+        let mut env = HashMap::from([(
+            "DOCKER_CONFIG".to_string(),
+            config.to_str().unwrap().to_string(),
+        )]);
+        let resolve = |flag: Option<&str>, env: &HashMap<String, String>| {
+            DockerEndpoint::resolve(flag, |name| env.get(name).cloned()).unwrap()
+        };
+
+        let endpoint = resolve(None, &env);
+        assert_eq!(endpoint.host, "tcp://builder:2376");
+        assert_eq!(
+            endpoint.tls_cert_path,
+            Some(config.join("contexts/tls/0a1b2c/docker"))
+        );
+
+        env.insert("DOCKER_HOST".to_string(), "tcp://remote:2376".to_string());
+        env.insert("DOCKER_TLS_VERIFY".to_string(), "1".to_string());
+        env.insert("DOCKER_CERT_PATH".to_string(), "/certs".to_string());
+        let endpoint = resolve(None, &env);
+        assert_eq!(endpoint.host, "tcp://remote:2376");
+        assert_eq!(endpoint.tls_cert_path, Some("/certs".into()));
+
+        let endpoint = resolve(Some("unix:///run/user/1000/docker.sock"), &env);
+        assert_eq!(endpoint.host, "unix:///run/user/1000/docker.sock");
+        assert_eq!(endpoint.source, "--docker-host");
+
+        env.clear();
+        env.insert(
+            "DOCKER_CONFIG".to_string(),
+            config.to_str().unwrap().to_string(),
+        );
+        env.insert("DOCKER_CONTEXT".to_string(), "missing".to_string());
+        assert!(DockerEndpoint::resolve(None, |name| env.get(name).cloned()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_podman_socket_is_used_without_docker() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let socket = temp_dir.path().join("podman/podman.sock");
+        fs::create_dir_all(socket.parent().unwrap()).unwrap();
+        fs::write(&socket, "").unwrap();
+
+        let env = HashMap::from([(
+            "XDG_RUNTIME_DIR".to_string(),
+            temp_dir.path().to_str().unwrap().to_string(),
+        )]);
+        let endpoint = DockerEndpoint::resolve(None, |name| env.get(name).cloned()).unwrap();
+        // A machine with Docker installed keeps using it
+        if std::path::Path::new("/var/run/docker.sock").exists() {
+            assert_eq!(endpoint.host, "unix:///var/run/docker.sock");
+        } else {
+            assert_eq!(endpoint.host, format!("unix://{}", socket.display()));
+        }
+    }
+}
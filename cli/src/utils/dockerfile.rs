@@ -0,0 +1,414 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// A single instruction of a Dockerfile, with line continuations joined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DockerfileInstruction {
+    /// The line the instruction starts on, counting from 1.
+    pub line: usize,
+    /// The instruction in upper case, e.g. `COPY`.
+    pub keyword: String,
+    /// The leading `--name=value` flags, a flag without a value has an empty value.
+    pub flags: Vec<(String, String)>,
+    /// The arguments after the flags, from the JSON array in exec form or split on whitespace.
+    pub arguments: Vec<String>,
+    /// Everything after the keyword as it was written.
+    pub raw: String,
+}
+
+impl DockerfileInstruction {
+    /// Returns the value of the given flag, e.g. `from` for `--from=builder`.
+    pub fn flag(&self, name: &str) -> Option<&str> {
+        self.flags
+            .iter()
+            .find(|(flag, _)| flag == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// An image a stage of the Dockerfile is built `FROM`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BaseImage {
+    /// The line of the `FROM` instruction.
+    pub line: usize,
+    /// The image with global build arguments substituted, e.g. `node:20-alpine`.
+    pub image: String,
+    /// The name given to the stage with `AS`.
+    pub stage: Option<String>,
+}
+
+/// A path in the build context that a `COPY` or `ADD` instruction reads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopySource {
+    /// The line of the instruction.
+    pub line: usize,
+    /// `COPY` or `ADD`.
+    pub keyword: String,
+    /// The source as it was written, relative to the build context.
+    pub source: String,
+}
+
+impl CopySource {
+    /// Returns the part of the source before the first wildcard as a path relative to the build
+    /// context, so `src/*.js` gives `src` and `.` or `*.json` give an empty path. Docker treats
+    /// absolute sources as relative to the context as well.
+    ///
+    /// # Returns
+    /// * `Some(PathBuf)` containing the path without `.` components.
+    /// * `None` if the source reaches outside the build context with `..`.
+    pub fn context_path(&self) -> Option<PathBuf> {
+        let mut path = PathBuf::new();
+        for component in self.source.split('/') {
+            if component.contains(['*', '?', '[']) {
+                break;
+            }
+            match component {
+                "" | "." => {}
+                ".." => {
+                    if !path.pop() {
+                        return None;
+                    }
+                }
+                component => path.push(component),
+            }
+        }
+        Some(path)
+    }
+}
+
+/// A parsed Dockerfile. Only what dockem needs is understood: instructions, their flags and
+/// arguments, line continuations, comments and the `escape` parser directive. Heredocs are not
+/// supported.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dockerfile {
+    pub instructions: Vec<DockerfileInstruction>,
+}
+
+impl Dockerfile {
+    /// Reads and parses the Dockerfile at the given path.
+    ///
+    /// # Arguments
+    /// * `path` - The path of the Dockerfile.
+    ///
+    /// # Returns
+    /// * `Ok(Dockerfile)` containing the parsed instructions.
+    /// * `Err(anyhow::Error)` if the file could not be read.
+    pub fn read(path: &str) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Unable to read the Dockerfile '{}'", path))?;
+        Ok(Self::parse(&contents))
+    }
+
+    /// Parses the contents of a Dockerfile.
+    pub fn parse(contents: &str) -> Self {
+        let escape = escape_directive(contents).unwrap_or('\\');
+        let mut instructions = Vec::new();
+        let mut pending: Option<(usize, String)> = None;
+
+        for (index, line) in contents.lines().enumerate() {
+            let trimmed = line.trim();
+            // Comments and empty lines are removed even in the middle of a continued instruction
+            if trimmed.starts_with('#') || trimmed.is_empty() {
+                continue;
+            }
+
+            let (text, continues) = match trimmed.strip_suffix(escape) {
+                Some(text) => (text, true),
+                None => (trimmed, false),
+            };
+            let (start, mut joined) = pending.take().unwrap_or((index + 1, String::new()));
+            if !joined.is_empty() && !text.is_empty() {
+                joined.push(' ');
+            }
+            joined.push_str(text.trim_end());
+
+            if continues {
+                pending = Some((start, joined));
+            } else if let Some(instruction) = parse_instruction(start, &joined) {
+                instructions.push(instruction);
+            }
+        }
+        if let Some((start, joined)) = pending {
+            instructions.extend(parse_instruction(start, &joined));
+        }
+
+        Dockerfile { instructions }
+    }
+
+    /// Returns the instructions with the given keyword, e.g. `COPY`.
+    pub fn instructions_named<'a>(
+        &'a self,
+        keyword: &'a str,
+    ) -> impl Iterator<Item = &'a DockerfileInstruction> + 'a {
+        self.instructions
+            .iter()
+            .filter(move |instruction| instruction.keyword == keyword)
+    }
+
+    /// Returns the sources of the `COPY` and `ADD` instructions that read from the build context.
+    /// Instructions with `--from` read from another stage or image, and `ADD` of a URL or git
+    /// repository reads from the network, so both are left out.
+    pub fn copy_sources(&self) -> Vec<CopySource> {
+        self.instructions
+            .iter()
+            .filter(|instruction| matches!(instruction.keyword.as_str(), "COPY" | "ADD"))
+            .filter(|instruction| instruction.flag("from").is_none())
+            .flat_map(|instruction| {
+                let sources = match instruction.arguments.split_last() {
+                    Some((_, sources)) => sources,
+                    None => &[],
+                };
+                sources
+                    .iter()
+                    .filter(|source| !source.starts_with("<<") && !is_remote_source(source))
+                    .map(|source| CopySource {
+                        line: instruction.line,
+                        keyword: instruction.keyword.clone(),
+                        source: source.clone(),
+                    })
+            })
+            .collect()
+    }
+
+    /// Returns the images the stages are built from. `scratch` and earlier stages are left out
+    /// because they are not pulled from a registry. Build arguments declared before the first
+    /// `FROM` are substituted with their defaults.
+    pub fn base_images(&self) -> Vec<BaseImage> {
+        let mut global_args = HashMap::new();
+        let mut stages: Vec<String> = Vec::new();
+        let mut images = Vec::new();
+        let mut seen_from = false;
+
+        for instruction in &self.instructions {
+            match instruction.keyword.as_str() {
+                "ARG" if !seen_from => {
+                    for argument in &instruction.arguments {
+                        let (name, value) = argument.split_once('=').unwrap_or((argument, ""));
+                        global_args.insert(name.to_string(), unquote(value).to_string());
+                    }
+                }
+                "FROM" => {
+                    seen_from = true;
+                    let Some(image) = instruction.arguments.first() else {
+                        continue;
+                    };
+                    let image = substitute_args(image, &global_args);
+                    let stage = match instruction.arguments.get(1..3) {
+                        Some([as_keyword, name]) if as_keyword.eq_ignore_ascii_case("as") => {
+                            Some(name.to_lowercase())
+                        }
+                        _ => None,
+                    };
+
+                    let from_stage = stages.contains(&image.to_lowercase());
+                    if let Some(stage) = &stage {
+                        stages.push(stage.clone());
+                    }
+                    if !from_stage && !image.eq_ignore_ascii_case("scratch") {
+                        images.push(BaseImage {
+                            line: instruction.line,
+                            image,
+                            stage,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        images
+    }
+}
+
+/// Whether an `ADD` source is downloaded instead of read from the build context.
+pub fn is_remote_source(source: &str) -> bool {
+    ["http://", "https://", "git@", "git://"]
+        .iter()
+        .any(|prefix| source.starts_with(prefix))
+}
+
+/// Reads the `# escape=` parser directive, which must come before any other line.
+fn escape_directive(contents: &str) -> Option<char> {
+    for line in contents.lines() {
+        let directive = line.trim().strip_prefix('#')?;
+        let (name, value) = directive.split_once('=')?;
+        if name.trim().eq_ignore_ascii_case("escape") {
+            return value.trim().chars().next();
+        }
+    }
+    None
+}
+
+fn parse_instruction(line: usize, text: &str) -> Option<DockerfileInstruction> {
+    let (keyword, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    if keyword.is_empty() {
+        return None;
+    }
+    let raw = rest.trim().to_string();
+
+    let mut flags = Vec::new();
+    let mut remainder = raw.as_str();
+    while let Some(flag) = remainder.strip_prefix("--") {
+        let (flag, rest) = flag.split_once(char::is_whitespace).unwrap_or((flag, ""));
+        let (name, value) = flag.split_once('=').unwrap_or((flag, ""));
+        flags.push((name.to_string(), value.to_string()));
+        remainder = rest.trim_start();
+    }
+
+    let arguments = if remainder.starts_with('[') {
+        serde_json::from_str::<Vec<String>>(remainder).ok()
+    } else {
+        None
+    }
+    .unwrap_or_else(|| remainder.split_whitespace().map(String::from).collect());
+
+    Some(DockerfileInstruction {
+        line,
+        keyword: keyword.to_uppercase(),
+        flags,
+        arguments,
+        raw,
+    })
+}
+
+/// Replaces `$NAME`, `${NAME}` and `${NAME:-default}` with the values of build arguments.
+/// Unknown arguments are left as they are.
+fn substitute_args(value: &str, args: &HashMap<String, String>) -> String {
+    let mut result = String::new();
+    let mut rest = value;
+    while let Some(position) = rest.find('$') {
+        result.push_str(&rest[..position]);
+        let after = &rest[position + 1..];
+
+        let (expression, consumed) = if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            }
+        } else {
+            let end = after
+                .find(|character: char| !(character.is_alphanumeric() || character == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], end)
+        };
+        let (name, default) = match expression.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (expression, None),
+        };
+
+        let value = args
+            .get(name)
+            .map(String::as_str)
+            .filter(|value| !value.is_empty())
+            .or(default);
+        match value {
+            Some(value) if consumed > 0 => result.push_str(value),
+            _ => result.push_str(&rest[position..position + 1 + consumed]),
+        }
+        rest = &after[consumed..];
+    }
+    result.push_str(rest);
+    result
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{BaseImage, Dockerfile};
+
+    #[test]
+    fn test_instructions_are_parsed_with_continuations_and_flags() {
+        let dockerfile = Dockerfile::parse(
+            "# syntax=docker/dockerfile:1\n\
+             FROM node:20 AS build\n\
+             RUN apt-get update && \\\n\
+             # a comment inside the instruction\n\
+             \x20   apt-get install -y git\n\
+             \n\
+             copy --from=build --chown=node /app/dist /srv\n\
+             CMD [\"node\", \"main.js\"]\n",
+        );
+
+        let keywords: Vec<&str> = dockerfile
+            .instructions
+            .iter()
+            .map(|instruction| instruction.keyword.as_str())
+            .collect();
+        assert_eq!(keywords, vec!["FROM", "RUN", "COPY", "CMD"]);
+
+        let run = &dockerfile.instructions[1];
+        assert_eq!(run.line, 3);
+        assert_eq!(run.raw, "apt-get update && apt-get install -y git");
+
+        let copy = &dockerfile.instructions[2];
+        assert_eq!(copy.flag("from"), Some("build"));
+        assert_eq!(copy.flag("chown"), Some("node"));
+        assert_eq!(copy.arguments, vec!["/app/dist", "/srv"]);
+        assert_eq!(
+            dockerfile.instructions[3].arguments,
+            vec!["node", "main.js"]
+        );
+    }
+
+    #[test]
+    fn test_copy_sources_skip_other_stages_and_urls() {
+        let dockerfile = Dockerfile::parse(
+            "FROM node:20 AS build\n\
+             COPY package.json package-lock.json ./\n\
+             ADD https://example.com/tool.tar.gz /opt/\n\
+             COPY [\"src\", \"/app/src\"]\n\
+             FROM nginx\n\
+             COPY --from=build /app/dist /srv\n",
+        );
+
+        let sources: Vec<(usize, String)> = dockerfile
+            .copy_sources()
+            .into_iter()
+            .map(|source| (source.line, source.source))
+            .collect();
+        assert_eq!(
+            sources,
+            vec![
+                (2, "package.json".to_string()),
+                (2, "package-lock.json".to_string()),
+                (4, "src".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_base_images_skip_stages_and_substitute_args() {
+        let dockerfile = Dockerfile::parse(
+            "ARG NODE_VERSION=20\n\
+             ARG REGISTRY\n\
+             FROM --platform=$BUILDPLATFORM node:${NODE_VERSION}-alpine AS build\n\
+             FROM ${REGISTRY:-docker.io}/library/nginx:1.27\n\
+             COPY --from=build /app /usr/share/nginx/html\n\
+             FROM build AS test\n\
+             FROM scratch\n",
+        );
+
+        assert_eq!(
+            dockerfile.base_images(),
+            vec![
+                BaseImage {
+                    line: 3,
+                    image: "node:20-alpine".to_string(),
+                    stage: Some("build".to_string()),
+                },
+                BaseImage {
+                    line: 4,
+                    image: "docker.io/library/nginx:1.27".to_string(),
+                    stage: None,
+                },
+            ]
+        );
+    }
+}
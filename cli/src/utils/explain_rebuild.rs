@@ -0,0 +1,115 @@
+use crate::utils::{
+    classify_manifest_error, fetch_hash_manifest, HashManifest, ManifestDiff, ManifestStatus,
+};
+use oci_client::client::Client as RegistryClient;
+use oci_client::secrets::RegistryAuth;
+use oci_client::Reference;
+use std::str::FromStr;
+use tracing::{debug, info, warn};
+
+/// The number of paths per kind of change that are logged at the info level.
+const LOGGED_PATHS: usize = 20;
+
+/// Explains why the image is rebuilt by diffing its hash manifest against the manifest attached
+/// to the image it replaces, see `previous_image_name`. Failing to fetch the previous manifest
+/// never fails the build.
+///
+/// # Arguments
+/// * `manifest` - The hash manifest of the image that is about to be built.
+/// * `previous_image` - The full name of the image this build replaces.
+/// * `registry_client` - An authenticated instance of the OCI registry client.
+///
+/// # Returns
+/// * `Some(ManifestDiff)` if a previous manifest was found.
+/// * `None` if there is no previous manifest or it could not be fetched.
+pub async fn explain_rebuild(
+    manifest: &HashManifest,
+    previous_image: &str,
+    registry_client: &RegistryClient,
+) -> Option<ManifestDiff> {
+    let reference = match Reference::from_str(previous_image) {
+        Ok(reference) => reference,
+        Err(error) => {
+            warn!(
+                "Unable to look up the previous image {}: {}",
+                previous_image, error
+            );
+            return None;
+        }
+    };
+    let digest = match registry_client
+        .fetch_manifest_digest(&reference, &RegistryAuth::Anonymous)
+        .await
+        .map_err(classify_manifest_error)
+    {
+        Ok(digest) => digest,
+        Err(ManifestStatus::Error(error)) => {
+            warn!(
+                "Unable to look up the previous image {}: {}",
+                previous_image, error
+            );
+            return None;
+        }
+        Err(_) => {
+            info!(
+                "No image was published to {} yet, this looks like the first build.",
+                previous_image
+            );
+            return None;
+        }
+    };
+
+    let tag = HashManifest::tag_for(&digest);
+    let previous = match fetch_hash_manifest(&reference, &tag, registry_client).await {
+        Ok(Some(previous)) => previous,
+        Ok(None) => {
+            info!(
+                "The previous image {} has no hash manifest, so the rebuild cannot be explained.",
+                previous_image
+            );
+            return None;
+        }
+        Err(error) => {
+            warn!("Unable to fetch the previous hash manifest: {:#}", error);
+            return None;
+        }
+    };
+
+    let diff = manifest.diff(&previous);
+    if diff.is_empty() {
+        info!(
+            "The files match the last build ({}), the hash inputs or the hashing scheme changed.",
+            previous.image_hash
+        );
+        return Some(diff);
+    }
+
+    info!(
+        "Rebuilding because {} files were added, {} removed and {} changed since the last build ({}).",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed.len(),
+        previous.image_hash
+    );
+    for (change, paths) in [
+        ("added", &diff.added),
+        ("removed", &diff.removed),
+        ("changed", &diff.changed),
+    ] {
+        for (index, path) in paths.iter().enumerate() {
+            if index < LOGGED_PATHS {
+                info!("  {}: {}", change, path);
+            } else {
+                debug!("  {}: {}", change, path);
+            }
+        }
+        if paths.len() > LOGGED_PATHS {
+            info!(
+                "  ... and {} more {} files, use -v to list them",
+                paths.len() - LOGGED_PATHS,
+                change
+            );
+        }
+    }
+    Some(diff)
+}
@@ -2,6 +2,7 @@ use crate::utils::os_open;
 use crate::utils::parse_version_file_json;
 use std::io;
 use std::io::Read;
+use tracing::{error, info};
 
 /// Expects a path to a JSON file that contains a `version` key. This key is parsed and returned
 /// with a `v` prefix.
@@ -15,7 +16,7 @@ use std::io::Read;
 pub fn extract_version(version_file_path: &str) -> Result<String, io::Error> {
     // Open the version file
     let mut version_file = os_open(version_file_path).map_err(|err| {
-        eprintln!(
+        error!(
             "Failed to open version file '{}': {}",
             version_file_path, err
         );
@@ -25,7 +26,7 @@ pub fn extract_version(version_file_path: &str) -> Result<String, io::Error> {
     // Read the file content into a byte vector
     let mut bytes = Vec::new();
     version_file.read_to_end(&mut bytes).map_err(|err| {
-        eprintln!(
+        error!(
             "Failed to read version file '{}': {}",
             version_file_path, err
         );
@@ -34,14 +35,14 @@ pub fn extract_version(version_file_path: &str) -> Result<String, io::Error> {
 
     // Parse the version from the JSON content
     let parsed_version = parse_version_file_json(&bytes).map_err(|err| {
-        eprintln!(
-            "ERROR: Failed to parse version file. '{}': {}",
+        error!(
+            "Failed to parse version file. '{}': {}",
             version_file_path, err
         );
         err
     })?;
 
     let version = "v".to_owned() + &parsed_version.version;
-    println!("The version of the image being built is: {}", version);
+    info!("The version of the image being built is: {}", version);
     Ok(version)
 }
@@ -0,0 +1,55 @@
+use crate::utils::{
+    classify_manifest_error, HashManifest, ManifestStatus, HASH_MANIFEST_MEDIA_TYPE,
+};
+use anyhow::{anyhow, Context, Result};
+use oci_client::client::Client as RegistryClient;
+use oci_client::secrets::RegistryAuth;
+use oci_client::Reference;
+
+/// Fetches a hash manifest pushed by `push_hash_manifest`.
+///
+/// # Arguments
+/// * `reference` - The reference of the hashed image, only its registry and repository are used.
+/// * `tag` - The tag of the hash manifest, see `HashManifest::tag_for`.
+/// * `registry_client` - An authenticated instance of the OCI registry client.
+///
+/// # Returns
+/// * `Ok(Some(HashManifest))` if the manifest exists.
+/// * `Ok(None)` if no manifest was pushed under the tag yet.
+/// * `Err(anyhow::Error)` if the registry could not be asked or the manifest is invalid.
+pub async fn fetch_hash_manifest(
+    reference: &Reference,
+    tag: &str,
+    registry_client: &RegistryClient,
+) -> Result<Option<HashManifest>> {
+    let target = Reference::with_tag(
+        reference.registry().to_string(),
+        reference.repository().to_string(),
+        tag.to_string(),
+    );
+
+    let image_data = match registry_client
+        .pull(
+            &target,
+            &RegistryAuth::Anonymous,
+            vec![HASH_MANIFEST_MEDIA_TYPE],
+        )
+        .await
+    {
+        Ok(image_data) => image_data,
+        Err(error) => {
+            return match classify_manifest_error(error) {
+                ManifestStatus::Error(error) => Err(error.into()),
+                _ => Ok(None),
+            }
+        }
+    };
+
+    let layer = image_data
+        .layers
+        .first()
+        .ok_or_else(|| anyhow!("The hash manifest {} has no layers", target))?;
+    let manifest = serde_json::from_slice(&layer.data)
+        .with_context(|| format!("The hash manifest {} is not valid", target))?;
+    Ok(Some(manifest))
+}
@@ -0,0 +1,343 @@
+use merkle_hash::blake3::Hash;
+use std::collections::BTreeMap;
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+/// The directory dockem keeps its local state in. It is never part of a hashed directory.
+pub const DOCKEM_DIRECTORY: &str = ".dockem";
+
+/// The default location of the hash cache, relative to the project root. Both the CLI and
+/// `BuildDockerImageParams::builder` use it unless the cache is turned off.
+pub const DEFAULT_HASH_CACHE_DIRECTORY: &str = ".dockem/cache";
+
+const CACHE_FILE_NAME: &str = "file-digests";
+const CACHE_HEADER: &str = "dockem-hash-cache v1";
+
+/// Files modified this close to the start of the run are hashed but not cached, because a change
+/// within the same timestamp tick would not be noticed next time.
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+/// The metadata a cached digest is valid for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CacheKey {
+    size: u64,
+    modified_nanos: u128,
+    inode: u64,
+}
+
+impl CacheKey {
+    fn from_metadata(metadata: &Metadata) -> Option<Self> {
+        let modified_nanos = metadata
+            .modified()
+            .ok()?
+            .duration_since(UNIX_EPOCH)
+            .ok()?
+            .as_nanos();
+
+        #[cfg(unix)]
+        let inode = std::os::unix::fs::MetadataExt::ino(metadata);
+        #[cfg(not(unix))]
+        let inode = 0;
+
+        Some(CacheKey {
+            size: metadata.len(),
+            modified_nanos,
+            inode,
+        })
+    }
+}
+
+/// An on-disk cache of the Blake3 digests of file contents, keyed by path, size, modification
+/// time and inode. A file whose metadata still matches is not read again, which makes hashing
+/// large build contexts that did not change fast. The digests are the same as hashing the
+/// contents, so results are identical with and without the cache. Entries are written sorted by
+/// path, so saving an unchanged cache writes the same file again.
+#[derive(Debug)]
+pub struct HashCache {
+    file: PathBuf,
+    started: SystemTime,
+    previous: BTreeMap<PathBuf, (CacheKey, Hash)>,
+    current: Mutex<BTreeMap<PathBuf, (CacheKey, Hash)>>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl HashCache {
+    /// Loads the cache from the given directory. A missing or unreadable cache starts empty.
+    ///
+    /// # Arguments
+    /// * `directory` - The directory the cache file lives in, e.g. `.dockem/cache`.
+    pub fn load(directory: impl AsRef<Path>) -> Self {
+        let file = directory.as_ref().join(CACHE_FILE_NAME);
+        let previous = match Self::read_entries(&file) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(error) => {
+                warn!(
+                    "Ignoring the hash cache {}: {}",
+                    file.to_string_lossy(),
+                    error
+                );
+                BTreeMap::new()
+            }
+        };
+        debug!(
+            "Loaded {} entries from the hash cache {}",
+            previous.len(),
+            file.to_string_lossy()
+        );
+
+        HashCache {
+            file,
+            started: SystemTime::now(),
+            previous,
+            current: Mutex::new(BTreeMap::new()),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the cached digest of the file contents, or computes and remembers it.
+    ///
+    /// # Arguments
+    /// * `path` - The path of the file, used as the cache key.
+    /// * `metadata` - The metadata of the file, the digest is only reused if it still matches.
+    /// * `compute` - Hashes the file contents on a cache miss.
+    ///
+    /// # Returns
+    /// * `Ok(Hash)` containing the Blake3 digest of the file contents.
+    /// * `Err(io::Error)` if the file had to be read and reading it failed.
+    pub fn content_digest<F>(
+        &self,
+        path: &Path,
+        metadata: &Metadata,
+        compute: F,
+    ) -> io::Result<Hash>
+    where
+        F: FnOnce() -> io::Result<Hash>,
+    {
+        let key = CacheKey::from_metadata(metadata);
+        if let (Some(key), Some((cached_key, digest))) = (&key, self.previous.get(path)) {
+            if key == cached_key {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                self.remember(path, key.clone(), *digest);
+                return Ok(*digest);
+            }
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let digest = compute()?;
+        if let Some(key) = key {
+            let racy = metadata
+                .modified()
+                .map(|modified| modified + RACY_WINDOW >= self.started)
+                .unwrap_or(true);
+            if !racy {
+                self.remember(path, key, digest);
+            }
+        }
+        Ok(digest)
+    }
+
+    /// The number of files whose digest was reused.
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// The number of files that had to be read.
+    pub fn misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Writes the cache back to disk. Entries from earlier runs that were not used this time are
+    /// kept as long as their file still exists.
+    ///
+    /// # Returns
+    /// * `Ok(())` if the cache was written.
+    /// * `Err(io::Error)` if the cache directory or file could not be written.
+    pub fn save(&self) -> io::Result<()> {
+        let current = self.current.lock().unwrap();
+        if let Some(parent) = self.file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Write to a temporary file first so that a crash never leaves a truncated cache
+        let temp_file = self.file.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&temp_file)?);
+        writeln!(writer, "{}", CACHE_HEADER)?;
+
+        let unused = self
+            .previous
+            .iter()
+            .filter(|(path, _)| !current.contains_key(*path) && path.exists());
+        for (path, (key, digest)) in current.iter().chain(unused) {
+            let Some(path) = path.to_str().filter(|path| !path.contains('\n')) else {
+                continue;
+            };
+            writeln!(
+                writer,
+                "{} {} {} {} {}",
+                digest.to_hex(),
+                key.size,
+                key.modified_nanos,
+                key.inode,
+                path
+            )?;
+        }
+        writer.into_inner().map_err(|error| error.into_error())?;
+        fs::rename(&temp_file, &self.file)?;
+
+        debug!(
+            "Hash cache: {} files reused, {} files read",
+            self.hits(),
+            self.misses()
+        );
+        Ok(())
+    }
+
+    fn remember(&self, path: &Path, key: CacheKey, digest: Hash) {
+        self.current
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), (key, digest));
+    }
+
+    fn read_entries(file: &Path) -> io::Result<BTreeMap<PathBuf, (CacheKey, Hash)>> {
+        let mut lines = BufReader::new(File::open(file)?).lines();
+        if lines.next().transpose()?.as_deref() != Some(CACHE_HEADER) {
+            return Err(io::Error::other("unknown cache format"));
+        }
+
+        let mut entries = BTreeMap::new();
+        for line in lines {
+            let line = line?;
+            let mut fields = line.splitn(5, ' ');
+            let mut next = || {
+                fields
+                    .next()
+                    .ok_or_else(|| io::Error::other("truncated cache entry"))
+            };
+            let invalid = |_| io::Error::other("invalid cache entry");
+
+            let digest = Hash::from_hex(next()?).map_err(|_| io::Error::other("invalid digest"))?;
+            let key = CacheKey {
+                size: next()?.parse().map_err(invalid)?,
+                modified_nanos: next()?.parse().map_err(invalid)?,
+                inode: next()?.parse().map_err(invalid)?,
+            };
+            entries.insert(PathBuf::from(next()?), (key, digest));
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::HashCache;
+    use merkle_hash::blake3;
+    use std::fs::{self, File};
+    use std::io;
+    use std::time::{Duration, SystemTime};
+    use tempfile::TempDir;
+
+    /// Moves the modification time out of the window in which files are not cached.
+    fn age(path: &std::path::Path) {
+        let file = File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(60))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_digest_is_reused_until_the_file_changes() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let cache_dir = temp_dir.path().join("cache");
+        let file = temp_dir.path().join("main.rs");
+        fs::write(&file, "fn main() {}").unwrap();
+        age(&file);
+
+        let digest = |cache: &HashCache| {
+            let metadata = fs::metadata(&file).unwrap();
+            cache
+                .content_digest(&file, &metadata, || Ok(blake3::hash(&fs::read(&file)?)))
+                .unwrap()
+        };
+
+        let cold = HashCache::load(&cache_dir);
+        let expected = digest(&cold);
+        assert_eq!(cold.misses(), 1);
+        cold.save().unwrap();
+
+        let warm = HashCache::load(&cache_dir);
+        let metadata = fs::metadata(&file).unwrap();
+        let cached = warm
+            .content_digest(&file, &metadata, || Err(io::Error::other("not cached")))
+            .unwrap();
+        assert_eq!(cached, expected);
+        assert_eq!(warm.hits(), 1);
+        warm.save().unwrap();
+
+        fs::write(&file, "fn main() { println!(); }").unwrap();
+        age(&file);
+        let changed = HashCache::load(&cache_dir);
+        assert_ne!(digest(&changed), expected);
+        assert_eq!(changed.misses(), 1);
+    }
+
+    #[test]
+    fn test_entries_are_saved_sorted_by_path() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let cache_dir = temp_dir.path().join("cache");
+        let cache = HashCache::load(&cache_dir);
+        for name in ["c.js", "a.js", "b.js", "e.js", "d.js"] {
+            let file = temp_dir.path().join(name);
+            fs::write(&file, name).unwrap();
+            age(&file);
+            let metadata = fs::metadata(&file).unwrap();
+            cache
+                .content_digest(&file, &metadata, || Ok(blake3::hash(name.as_bytes())))
+                .unwrap();
+        }
+        cache.save().unwrap();
+
+        let contents = fs::read_to_string(cache_dir.join("file-digests")).unwrap();
+        let paths: Vec<&str> = contents
+            .lines()
+            .skip(1)
+            .map(|line| line.rsplit('/').next().unwrap())
+            .collect();
+        assert_eq!(paths, vec!["a.js", "b.js", "c.js", "d.js", "e.js"]);
+
+        // Saving again without changes writes the same file
+        HashCache::load(&cache_dir).save().unwrap();
+        assert_eq!(
+            fs::read_to_string(cache_dir.join("file-digests")).unwrap(),
+            contents
+        );
+    }
+
+    #[test]
+    fn test_recently_modified_files_are_not_cached() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let cache_dir = temp_dir.path().join("cache");
+        let file = temp_dir.path().join("main.rs");
+        fs::write(&file, "fn main() {}").unwrap();
+
+        let cache = HashCache::load(&cache_dir);
+        let metadata = fs::metadata(&file).unwrap();
+        cache
+            .content_digest(&file, &metadata, || Ok(blake3::hash(b"fn main() {}")))
+            .unwrap();
+        cache.save().unwrap();
+
+        let cache = HashCache::load(&cache_dir);
+        cache
+            .content_digest(&file, &metadata, || Ok(blake3::hash(b"fn main() {}")))
+            .unwrap();
+        assert_eq!(cache.hits(), 0);
+    }
+}
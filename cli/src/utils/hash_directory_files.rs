@@ -0,0 +1,315 @@
+use crate::utils::{HashCache, DOCKEM_DIRECTORY};
+use anyhow::{anyhow, Context, Result};
+use merkle_hash::blake3::{self, Hash, Hasher};
+use rayon::prelude::*;
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Hashes the given directory and also returns the hash of every file in it. Every entry is
+/// hashed as `name || type || payload` and the payload of a directory is the merkle hash of its
+/// children sorted by path, see "Hashing Spec" in the README:
+/// * Regular files have the type `f`, or `x` if any executable bit is set, and the digest of their
+///   contents as the payload, which can come from a `HashCache`.
+/// * Symlinks are not followed, they have the type `l` and the digest of their target.
+/// * Directories have the type `d`, the directory itself is named `root_name`.
+/// * Sockets, FIFOs and device files cannot be sent to Docker and are an error.
+///
+/// `.dockem` directories are skipped at any depth so that the cache never changes the hash of the
+/// directory it lives in, as are the `excluded` paths, which are used to leave out secrets.
+///
+/// # Arguments
+/// * `directory` - The directory to be hashed.
+/// * `root_name` - The name hashed for the directory itself, usually its project path.
+/// * `cache` - An optional cache of file content digests.
+/// * `excluded` - Paths relative to the directory that are not hashed.
+///
+/// # Returns
+/// * `Ok((String, Vec<(String, String)>))` containing the directory hash and the relative path and
+///   hash of every file and symlink, sorted by path.
+/// * `Err(anyhow::Error)` if any file operation fails or a special file is found.
+pub fn hash_directory_files(
+    directory: &str,
+    root_name: &str,
+    cache: Option<&HashCache>,
+    excluded: &[PathBuf],
+) -> Result<(String, Vec<(String, String)>)> {
+    let files = Mutex::new(Vec::new());
+    let hash = hash_node(
+        Path::new(directory),
+        Path::new(""),
+        root_name,
+        cache,
+        excluded,
+        &files,
+    )?;
+
+    let mut files = files.into_inner().unwrap();
+    files.sort();
+    Ok((hash.to_hex().to_string(), files))
+}
+
+/// Hashes a file, symlink or directory as `name || type || payload`.
+fn hash_node(
+    path: &Path,
+    relative: &Path,
+    root_name: &str,
+    cache: Option<&HashCache>,
+    excluded: &[PathBuf],
+    files: &Mutex<Vec<(String, String)>>,
+) -> Result<Hash> {
+    let is_root = relative.as_os_str().is_empty();
+    // The directory that was passed in may be a symlink itself, anything inside it is not followed
+    let metadata = if is_root {
+        fs::metadata(path)
+    } else {
+        fs::symlink_metadata(path)
+    }
+    .with_context(|| format!("Unable to read the metadata of {}", path.display()))?;
+    let file_type = metadata.file_type();
+
+    let (entry_type, payload) = if file_type.is_dir() {
+        let mut children = fs::read_dir(path)
+            .with_context(|| format!("Unable to read the directory {}", path.display()))?
+            .par_bridge()
+            .filter(|entry| !matches!(entry, Ok(entry) if entry.file_name() == DOCKEM_DIRECTORY))
+            .filter(|entry| {
+                !matches!(entry, Ok(entry) if excluded.contains(&relative.join(entry.file_name())))
+            })
+            .map(|entry| {
+                let entry = entry
+                    .with_context(|| format!("Unable to read the directory {}", path.display()))?;
+                let child_relative = relative.join(entry.file_name());
+                let hash = hash_node(
+                    &entry.path(),
+                    &child_relative,
+                    root_name,
+                    cache,
+                    excluded,
+                    files,
+                )?;
+                Ok((child_relative, hash))
+            })
+            .collect::<Result<Vec<(PathBuf, Hash)>>>()?;
+        children.sort_by(|first, second| first.0.cmp(&second.0));
+
+        let hashes: Vec<Hash> = children.into_iter().map(|(_, hash)| hash).collect();
+        (
+            b"d",
+            merkle_hash_of(&hashes).unwrap_or_else(|| blake3::hash(b"")),
+        )
+    } else if file_type.is_symlink() {
+        let target = fs::read_link(path)
+            .with_context(|| format!("Unable to read the symlink {}", path.display()))?;
+        (b"l", blake3::hash(target.as_os_str().as_encoded_bytes()))
+    } else if file_type.is_file() {
+        let read_contents = || Ok(blake3::hash(&fs::read(path)?));
+        let digest = match cache {
+            Some(cache) => cache.content_digest(path, &metadata, read_contents),
+            None => read_contents(),
+        }
+        .with_context(|| format!("Unable to read the file {}", path.display()))?;
+        (if is_executable(&metadata) { b"x" } else { b"f" }, digest)
+    } else {
+        return Err(anyhow!(
+            "Unable to hash {}, only regular files, directories and symlinks can be in a build context",
+            path.display()
+        ));
+    };
+
+    let name = if is_root {
+        root_name
+    } else {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| anyhow!("Unable to read file name: {:?}", path))?
+    };
+    let mut hasher = Hasher::new();
+    hasher.update(name.as_bytes());
+    hasher.update(entry_type);
+    hasher.update(payload.as_bytes());
+    let hash = hasher.finalize();
+
+    if !file_type.is_dir() && !is_root {
+        let relative = relative
+            .to_str()
+            .ok_or_else(|| anyhow!("The path {:?} is not valid UTF-8", relative))?;
+        files
+            .lock()
+            .unwrap()
+            .push((relative.to_string(), hash.to_hex().to_string()));
+    }
+    Ok(hash)
+}
+
+/// Whether any executable bit is set. The other permission bits depend on the umask of whoever
+/// checked the files out, so like git only this one is hashed.
+fn is_executable(metadata: &Metadata) -> bool {
+    #[cfg(unix)]
+    let executable = std::os::unix::fs::PermissionsExt::mode(&metadata.permissions()) & 0o111 != 0;
+    #[cfg(not(unix))]
+    let executable = false;
+
+    executable
+}
+
+/// Combines hashes pairwise until one is left, an odd hash out is paired with itself.
+fn merkle_hash_of(hashes: &[Hash]) -> Option<Hash> {
+    match hashes {
+        [] => None,
+        [hash] => Some(*hash),
+        _ => {
+            let next_level: Vec<Hash> = hashes
+                .chunks(2)
+                .map(|pair| {
+                    let mut hasher = Hasher::new();
+                    hasher.update(pair[0].as_bytes());
+                    hasher.update(pair.get(1).unwrap_or(&pair[0]).as_bytes());
+                    hasher.finalize()
+                })
+                .collect();
+            merkle_hash_of(&next_level)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{hash_directory_files, HashCache};
+    use std::fs::{self, File};
+    use std::time::{Duration, SystemTime};
+    use tempfile::TempDir;
+
+    fn write_old(path: &std::path::Path, contents: &str) {
+        fs::write(path, contents).unwrap();
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(60))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_cached_hash_is_identical_to_a_cold_run() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let context = temp_dir.path().join("app");
+        fs::create_dir_all(context.join("src/nested")).unwrap();
+        fs::create_dir_all(context.join("empty")).unwrap();
+        write_old(&context.join("Dockerfile"), "FROM alpine");
+        write_old(&context.join("src/a.js"), "a");
+        write_old(&context.join("src/b.js"), "b");
+        write_old(&context.join("src/nested/c.js"), "c");
+        let context = context.to_str().unwrap();
+        let cache_dir = temp_dir.path().join("cache");
+
+        let cold = hash_directory_files(context, "app", None, &[]).unwrap();
+        assert_eq!(cold.1.len(), 4);
+
+        let cache = HashCache::load(&cache_dir);
+        assert_eq!(
+            hash_directory_files(context, "app", Some(&cache), &[]).unwrap(),
+            cold
+        );
+        cache.save().unwrap();
+
+        let cache = HashCache::load(&cache_dir);
+        assert_eq!(
+            hash_directory_files(context, "app", Some(&cache), &[]).unwrap(),
+            cold
+        );
+        assert_eq!(cache.hits(), 4);
+        assert_eq!(cache.misses(), 0);
+    }
+
+    #[test]
+    fn test_dockem_directory_is_not_hashed() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let context = temp_dir.path().to_str().unwrap();
+        fs::write(temp_dir.path().join("Dockerfile"), "FROM alpine").unwrap();
+        let before = hash_directory_files(context, ".", None, &[]).unwrap();
+
+        fs::create_dir_all(temp_dir.path().join(".dockem/cache")).unwrap();
+        fs::write(temp_dir.path().join(".dockem/cache/file-digests"), "x").unwrap();
+        assert_eq!(
+            hash_directory_files(context, ".", None, &[]).unwrap(),
+            before
+        );
+    }
+
+    #[test]
+    fn test_nested_dockem_directory_is_not_hashed() {
+        // dockem runs from a subdirectory of a watched directory and keeps its cache there
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let watched = temp_dir.path().join("libs");
+        fs::create_dir_all(watched.join("shared")).unwrap();
+        fs::write(watched.join("shared/index.js"), "export {}").unwrap();
+        let watched = watched.to_str().unwrap();
+        let before = hash_directory_files(watched, "libs", None, &[]).unwrap();
+
+        let cache_dir = temp_dir.path().join("libs/shared/.dockem/cache");
+        let cache = HashCache::load(&cache_dir);
+        let cold = hash_directory_files(watched, "libs", Some(&cache), &[]).unwrap();
+        cache.save().unwrap();
+        assert!(cache_dir.join("file-digests").exists());
+        assert_eq!(cold, before);
+
+        let cache = HashCache::load(&cache_dir);
+        assert_eq!(
+            hash_directory_files(watched, "libs", Some(&cache), &[]).unwrap(),
+            before
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_executable_bit_changes_the_hash() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let context = temp_dir.path().to_str().unwrap();
+        let entrypoint = temp_dir.path().join("entrypoint.sh");
+        fs::write(&entrypoint, "#!/bin/sh\nexec node main.js").unwrap();
+        fs::set_permissions(&entrypoint, fs::Permissions::from_mode(0o644)).unwrap();
+        let before = hash_directory_files(context, ".", None, &[]).unwrap();
+
+        // chmod +x
+        fs::set_permissions(&entrypoint, fs::Permissions::from_mode(0o755)).unwrap();
+        let executable = hash_directory_files(context, ".", None, &[]).unwrap();
+        assert_ne!(executable.0, before.0);
+        assert_ne!(executable.1, before.1);
+
+        // Only the executable bit counts, the rest depends on the umask
+        fs::set_permissions(&entrypoint, fs::Permissions::from_mode(0o775)).unwrap();
+        assert_eq!(
+            hash_directory_files(context, ".", None, &[]).unwrap(),
+            executable
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_are_hashed_by_target() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let context = temp_dir.path().join("app");
+        fs::create_dir_all(&context).unwrap();
+        fs::write(context.join("v1.conf"), "one").unwrap();
+        fs::write(context.join("v2.conf"), "two").unwrap();
+        std::os::unix::fs::symlink("v1.conf", context.join("current.conf")).unwrap();
+        let context = context.to_str().unwrap();
+        let before = hash_directory_files(context, ".", None, &[]).unwrap();
+
+        std::fs::remove_file(temp_dir.path().join("app/current.conf")).unwrap();
+        std::os::unix::fs::symlink("v2.conf", temp_dir.path().join("app/current.conf")).unwrap();
+        assert_ne!(
+            hash_directory_files(context, ".", None, &[]).unwrap().0,
+            before.0
+        );
+
+        // A link to a missing target is hashed like any other link
+        std::fs::remove_file(temp_dir.path().join("app/current.conf")).unwrap();
+        std::os::unix::fs::symlink("missing.conf", temp_dir.path().join("app/current.conf"))
+            .unwrap();
+        assert!(hash_directory_files(context, ".", None, &[]).is_ok());
+    }
+}
@@ -0,0 +1,305 @@
+use anyhow::{anyhow, Context, Result};
+use merkle_hash::blake3::Hasher;
+use std::collections::BTreeMap;
+use std::fs::{self, Metadata};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// The mode and object ID of a file as git records it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitEntry {
+    pub mode: String,
+    pub object_id: String,
+}
+
+/// Hashes the files git tracks in the given directory. Clean files are hashed from the blob IDs in
+/// the index, so the hash does not depend on untracked or ignored files and is the same in every
+/// clone. Files modified in the working tree are hashed from disk with `git hash-object`, which
+/// gives the blob ID they would have once committed. Deleted files are left out.
+///
+/// # Arguments
+/// * `directory` - The directory to be hashed, it must be inside a git working tree.
+/// * `excluded` - Paths relative to the directory that are not hashed, even if tracked.
+///
+/// # Returns
+/// * `Ok((String, Vec<(String, String)>))` containing the directory hash and the relative path and
+///   hash of every tracked file, sorted by path.
+/// * `Err(anyhow::Error)` if git could not be run or the directory is not in a git working tree.
+pub fn hash_git_directory(
+    directory: &str,
+    excluded: &[PathBuf],
+) -> Result<(String, Vec<(String, String)>)> {
+    let index = run_git(directory, &["ls-files", "--stage", "-z", "--", "."], None)?;
+    let mut entries = parse_ls_files(&index)?;
+    let is_excluded = |path: &str| excluded.iter().any(|excluded| Path::new(path) == excluded);
+    entries.retain(|path, _| !is_excluded(path));
+
+    let dirty = run_git(
+        directory,
+        &["diff-files", "--relative", "--name-only", "-z", "--", "."],
+        None,
+    )?;
+    let mut to_hash = Vec::new();
+    for path in split_nul(&dirty)?
+        .into_iter()
+        .filter(|path| !is_excluded(path))
+    {
+        match fs::symlink_metadata(Path::new(directory).join(&path)) {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                entries.remove(&path);
+            }
+            Err(error) => {
+                return Err(error).with_context(|| format!("Unable to read the file {}", path))
+            }
+            // A submodule with local changes keeps the commit recorded in the index
+            Ok(metadata) if metadata.is_dir() => {}
+            Ok(metadata) => {
+                let mode = git_mode(&metadata);
+                let object_id = if metadata.is_symlink() {
+                    hash_symlink(directory, &path)?
+                } else {
+                    to_hash.push(path.clone());
+                    String::new()
+                };
+                entries.insert(path, GitEntry { mode, object_id });
+            }
+        }
+    }
+
+    if !to_hash.is_empty() {
+        if to_hash.iter().any(|path| path.contains('\n')) {
+            return Err(anyhow!(
+                "Modified files with a newline in their name cannot be hashed"
+            ));
+        }
+        // hash-object resolves relative paths from the top of the working tree, not from `-C`
+        let root = fs::canonicalize(directory)
+            .with_context(|| format!("Unable to resolve the directory {}", directory))?;
+        let paths: String = to_hash
+            .iter()
+            .map(|path| format!("{}\n", root.join(path).to_string_lossy()))
+            .collect();
+        let output = run_git(
+            directory,
+            &["hash-object", "--stdin-paths"],
+            Some(paths.as_bytes()),
+        )?;
+        let object_ids = String::from_utf8(output).context("git returned an invalid object ID")?;
+        for (path, object_id) in to_hash.iter().zip(object_ids.lines()) {
+            if let Some(entry) = entries.get_mut(path) {
+                entry.object_id = object_id.to_string();
+            }
+        }
+    }
+
+    let mut directory_hasher = Hasher::new();
+    let files = entries
+        .into_iter()
+        .map(|(path, entry)| {
+            let mut hasher = Hasher::new();
+            hasher.update(entry.mode.as_bytes());
+            hasher.update(b" ");
+            hasher.update(entry.object_id.as_bytes());
+            let hash = hasher.finalize().to_hex().to_string();
+
+            directory_hasher.update(path.as_bytes());
+            directory_hasher.update(b"\0");
+            directory_hasher.update(hash.as_bytes());
+            directory_hasher.update(b"\0");
+            (path, hash)
+        })
+        .collect();
+
+    Ok((directory_hasher.finalize().to_hex().to_string(), files))
+}
+
+/// Parses the output of `git ls-files --stage -z`. Files with merge conflicts are listed once per
+/// stage, only the last stage is kept because conflicted files are also modified and rehashed.
+///
+/// # Arguments
+/// * `output` - The NUL separated `<mode> <object> <stage>\t<path>` records.
+///
+/// # Returns
+/// * `Ok(BTreeMap<String, GitEntry>)` mapping every path to its mode and object ID.
+/// * `Err(anyhow::Error)` if a record is malformed.
+pub fn parse_ls_files(output: &[u8]) -> Result<BTreeMap<String, GitEntry>> {
+    split_nul(output)?
+        .into_iter()
+        .map(|record| {
+            let (info, path) = record
+                .split_once('\t')
+                .ok_or_else(|| anyhow!("Unexpected git ls-files output: {}", record))?;
+            let mut fields = info.split(' ');
+            match (fields.next(), fields.next()) {
+                (Some(mode), Some(object_id)) => Ok((
+                    path.to_string(),
+                    GitEntry {
+                        mode: mode.to_string(),
+                        object_id: object_id.to_string(),
+                    },
+                )),
+                _ => Err(anyhow!("Unexpected git ls-files output: {}", record)),
+            }
+        })
+        .collect()
+}
+
+fn split_nul(output: &[u8]) -> Result<Vec<String>> {
+    output
+        .split(|byte| *byte == 0)
+        .filter(|record| !record.is_empty())
+        .map(|record| {
+            String::from_utf8(record.to_vec()).context("git returned a path that is not UTF-8")
+        })
+        .collect()
+}
+
+/// The git file mode of a file on disk, only the bits git itself records.
+fn git_mode(metadata: &Metadata) -> String {
+    if metadata.is_symlink() {
+        return "120000".to_string();
+    }
+
+    #[cfg(unix)]
+    let executable = std::os::unix::fs::PermissionsExt::mode(&metadata.permissions()) & 0o111 != 0;
+    #[cfg(not(unix))]
+    let executable = false;
+
+    if executable { "100755" } else { "100644" }.to_string()
+}
+
+/// Git stores a symlink as a blob containing its target, `hash-object` would follow the link.
+fn hash_symlink(directory: &str, path: &str) -> Result<String> {
+    let target = fs::read_link(Path::new(directory).join(path))
+        .with_context(|| format!("Unable to read the symlink {}", path))?;
+    let target = target
+        .to_str()
+        .ok_or_else(|| anyhow!("The target of the symlink {} is not UTF-8", path))?;
+    let output = run_git(
+        directory,
+        &["hash-object", "--stdin"],
+        Some(target.as_bytes()),
+    )?;
+    Ok(String::from_utf8(output)?.trim().to_string())
+}
+
+fn run_git(directory: &str, args: &[&str], stdin: Option<&[u8]>) -> Result<Vec<u8>> {
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(directory)
+        .args(args)
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Unable to run git, it is required to hash from the git index")?;
+
+    if let (Some(input), Some(mut child_stdin)) = (stdin, child.stdin.take()) {
+        child_stdin.write_all(input)?;
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git {} failed in '{}': {}",
+            args.join(" "),
+            directory,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{hash_git_directory, parse_ls_files, GitEntry};
+    use std::fs;
+    use std::path::Path;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    fn git(directory: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(directory)
+            .args([
+                "-c",
+                "user.name=dockem",
+                "-c",
+                "user.email=dockem@example.com",
+            ])
+            .args(args)
+            .output()
+            .expect("Failed to run git")
+            .status;
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    #[test]
+    fn test_parse_ls_files() {
+        let output = b"100644 e69de29bb2d1d6434b8b29ae775ad8c2e48c5391 0\tsrc/main.rs\0\
+            100755 2e65efe2a145dda7ee51d1741299f848e5bf752e 0\tentrypoint.sh\0";
+        let entries = parse_ls_files(output).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries["entrypoint.sh"],
+            GitEntry {
+                mode: "100755".to_string(),
+                object_id: "2e65efe2a145dda7ee51d1741299f848e5bf752e".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_only_tracked_files_and_local_changes_count() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let repo = temp_dir.path().join("repo");
+        fs::create_dir_all(repo.join("app")).unwrap();
+        fs::write(repo.join(".gitignore"), "dist/\n").unwrap();
+        fs::write(repo.join("app/main.js"), "console.log(1)").unwrap();
+        git(temp_dir.path(), &["init", "-q", "repo"]);
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "-q", "-m", "initial"]);
+        let app = repo.join("app");
+        let app = app.to_str().unwrap();
+
+        let clean = hash_git_directory(app, &[]).unwrap();
+        assert_eq!(clean.1.len(), 1);
+
+        // Untracked and ignored files do not change the hash
+        fs::create_dir_all(repo.join("app/dist")).unwrap();
+        fs::write(repo.join("app/dist/bundle.js"), "built").unwrap();
+        fs::write(repo.join("app/notes.txt"), "untracked").unwrap();
+        assert_eq!(hash_git_directory(app, &[]).unwrap(), clean);
+
+        // Local changes are hashed from disk
+        fs::write(repo.join("app/main.js"), "console.log(2)").unwrap();
+        let dirty = hash_git_directory(app, &[]).unwrap();
+        assert_ne!(dirty.0, clean.0);
+
+        // Committing the change gives the same hash, so does a fresh clone
+        git(&repo, &["commit", "-q", "-am", "change"]);
+        assert_eq!(hash_git_directory(app, &[]).unwrap(), dirty);
+
+        let clone = temp_dir.path().join("clone");
+        git(
+            temp_dir.path(),
+            &[
+                "clone",
+                "-q",
+                repo.to_str().unwrap(),
+                clone.to_str().unwrap(),
+            ],
+        );
+        assert_eq!(
+            hash_git_directory(clone.join("app").to_str().unwrap(), &[]).unwrap(),
+            dirty
+        );
+    }
+}
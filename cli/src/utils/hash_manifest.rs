@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The media type of the layer that holds a serialized `HashManifest`.
+pub const HASH_MANIFEST_MEDIA_TYPE: &str = "application/vnd.dockem.hash-manifest.v1+json";
+
+/// The hash of every file that contributed to an image hash, keyed by path. It is pushed next to
+/// the hashed image so that a later build can explain why its hash changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HashManifest {
+    pub image_hash: String,
+    pub files: BTreeMap<String, String>,
+}
+
+/// The files that differ between two hash manifests.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ManifestDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl HashManifest {
+    /// The tag the manifest of an image is attached under, derived from the digest of the image
+    /// manifest like signature tags are, e.g. `sha256-<hex>.hashes`.
+    pub fn tag_for(image_digest: &str) -> String {
+        format!("{}.hashes", image_digest.replace(':', "-"))
+    }
+
+    /// Compares this manifest with the manifest of a previous build.
+    ///
+    /// # Arguments
+    /// * `previous` - The manifest of the image that was built before.
+    ///
+    /// # Returns
+    /// * `ManifestDiff` listing the added, removed and changed paths, each sorted.
+    pub fn diff(&self, previous: &HashManifest) -> ManifestDiff {
+        let mut diff = ManifestDiff::default();
+
+        for (path, hash) in &self.files {
+            match previous.files.get(path) {
+                None => diff.added.push(path.clone()),
+                Some(previous_hash) if previous_hash != hash => diff.changed.push(path.clone()),
+                Some(_) => {}
+            }
+        }
+
+        diff.removed = previous
+            .files
+            .keys()
+            .filter(|path| !self.files.contains_key(*path))
+            .cloned()
+            .collect();
+
+        diff
+    }
+}
+
+impl ManifestDiff {
+    /// Whether both manifests hash the same files to the same values.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{HashManifest, ManifestDiff};
+
+    fn manifest(files: &[(&str, &str)]) -> HashManifest {
+        HashManifest {
+            image_hash: String::new(),
+            files: files
+                .iter()
+                .map(|(path, hash)| (path.to_string(), hash.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_diff_lists_added_removed_and_changed_files() {
+        let previous = manifest(&[("Dockerfile", "a"), ("src/main.rs", "b"), ("old.rs", "c")]);
+        let current = manifest(&[("Dockerfile", "a"), ("src/main.rs", "x"), ("new.rs", "d")]);
+
+        assert_eq!(
+            current.diff(&previous),
+            ManifestDiff {
+                added: vec!["new.rs".to_string()],
+                removed: vec!["old.rs".to_string()],
+                changed: vec!["src/main.rs".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_manifest_tag_is_derived_from_the_image_digest() {
+        assert_eq!(
+            HashManifest::tag_for("sha256:1a2b3c"),
+            "sha256-1a2b3c.hashes"
+        );
+    }
+
+    #[test]
+    fn test_identical_manifests_have_an_empty_diff() {
+        let current = manifest(&[("Dockerfile", "a")]);
+        assert!(current.diff(&current.clone()).is_empty());
+    }
+}
@@ -0,0 +1,58 @@
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// The version of the algorithm the image hash is computed with. The hash of every scheme after
+/// the first starts with the scheme, e.g. `h2-<blake3>`, so that a change to the algorithm can
+/// never reuse or collide with an image hashed by another scheme.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashScheme {
+    /// The original scheme: paths as they were passed, directories hashed with `MerkleTree` and no
+    /// prefix. Only kept to reuse existing images while migrating to a newer scheme.
+    V1,
+    /// Paths relative to the project root, executable bits and symlink targets included, see
+    /// "Hashing Spec" in the README. Hashes are prefixed with `h2-`.
+    #[default]
+    V2,
+}
+
+impl HashScheme {
+    /// Turns the digest computed by this scheme into the image hash used as the tag.
+    ///
+    /// # Arguments
+    /// * `digest` - The hex encoded digest of all inputs.
+    ///
+    /// # Returns
+    /// * `String` the image hash, e.g. `h2-<digest>`.
+    pub fn image_hash(&self, digest: &str) -> String {
+        match self {
+            HashScheme::V1 => digest.to_string(),
+            HashScheme::V2 => format!("h2-{}", digest),
+        }
+    }
+}
+
+impl FromStr for HashScheme {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "v1" => Ok(HashScheme::V1),
+            "v2" => Ok(HashScheme::V2),
+            _ => Err(format!(
+                "Unknown hash scheme '{}', expected 'v1' or 'v2'",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for HashScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashScheme::V1 => write!(f, "v1"),
+            HashScheme::V2 => write!(f, "v2"),
+        }
+    }
+}
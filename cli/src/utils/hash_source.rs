@@ -0,0 +1,37 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Decides where the contents of the build and watch directories are read from when hashing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HashSource {
+    /// Walk the directories on disk and hash every file in them.
+    #[default]
+    Filesystem,
+    /// Hash the files tracked by git, using the blob IDs from the index for clean files and
+    /// hashing modified files from disk. Untracked and ignored files do not count.
+    Git,
+}
+
+impl FromStr for HashSource {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "filesystem" => Ok(HashSource::Filesystem),
+            "git" => Ok(HashSource::Git),
+            _ => Err(format!(
+                "Unknown hash source '{}', expected 'filesystem' or 'git'",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for HashSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashSource::Filesystem => write!(f, "filesystem"),
+            HashSource::Git => write!(f, "git"),
+        }
+    }
+}
@@ -1,9 +1,5 @@
-use merkle_hash::blake3::Hasher;
 use merkle_hash::error::IndexingError;
 use merkle_hash::{Algorithm, Encodable, MerkleTree};
-use rayon::prelude::*;
-use std::io;
-use std::path::Path;
 
 /// Hashes the given directory and its subdirectories and returns a combined hash.
 ///
@@ -25,6 +21,7 @@ pub fn hash_directory(directory: &str) -> Result<String, IndexingError> {
 }
 
 /// Hashes the given list of directories in parallel, including their subdirectories, and returns a combined hash.
+/// The tests use it as the reference for `HashScheme::V1`.
 ///
 /// # Arguments
 /// * `watch_directories` - A slice of directory paths to be hashed.
@@ -32,7 +29,14 @@ pub fn hash_directory(directory: &str) -> Result<String, IndexingError> {
 /// # Returns
 /// * `Ok(String)` containing the hash if successful.
 /// * `Err(io::Error)` if any file operation fails.
-pub fn hash_watch_directories(watch_directories: &Vec<String>) -> Result<String, io::Error> {
+#[cfg(test)]
+pub fn hash_watch_directories(watch_directories: &[String]) -> Result<String, std::io::Error> {
+    use merkle_hash::blake3::Hasher;
+    use rayon::prelude::*;
+    use std::io;
+    use std::path::Path;
+    use tracing::warn;
+
     if watch_directories.is_empty() {
         return Ok(String::new());
     }
@@ -49,17 +53,14 @@ pub fn hash_watch_directories(watch_directories: &Vec<String>) -> Result<String,
         .filter_map(|directory| match hash_directory(directory) {
             Ok(hash) => Some(hash),
             Err(err) => {
-                eprintln!("WARNING: Failed to hash directory '{}': {}", directory, err);
+                warn!("Failed to hash directory '{}': {}", directory, err);
                 None
             }
         })
         .collect();
 
     if hashes.len() != sorted_directories.len() {
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            "Failed to hash one or more directories.",
-        ));
+        return Err(io::Error::other("Failed to hash one or more directories."));
     }
 
     let combined_hash_string = hashes.join("");
@@ -1,5 +1,4 @@
 use merkle_hash::blake3::Hasher;
-use rayon::prelude::*;
 use std::fs;
 use std::io;
 
@@ -12,15 +11,29 @@ use std::io;
 /// * `Ok(String)` containing the hash if successful.
 /// * `Err(io::Error)` if any file operation fails.
 pub fn hash_file(file_path: &str) -> Result<String, io::Error> {
+    hash_file_named(file_path, file_path)
+}
+
+/// Hashes the given file with the given name included instead of the path it was read from.
+///
+/// # Arguments
+/// * `file_path` - The file to be hashed.
+/// * `name` - The name to include in the hash, usually the path relative to the project root.
+///
+/// # Returns
+/// * `Ok(String)` containing the hash if successful.
+/// * `Err(io::Error)` if any file operation fails.
+pub fn hash_file_named(file_path: &str, name: &str) -> Result<String, io::Error> {
     fs::read(file_path).map(|content| {
         let mut hasher = Hasher::new();
         hasher.update(&content);
-        hasher.update(file_path.as_bytes());
+        hasher.update(name.as_bytes());
         hasher.finalize().to_hex().to_string()
     })
 }
 
-/// Hashes the given list of files in parallel and returns a combined hash.
+/// Hashes the given list of files in parallel and returns a combined hash. The tests use it as
+/// the reference for `HashScheme::V1`.
 ///
 /// # Arguments
 /// * `watch_files` - A slice of file paths to be hashed.
@@ -28,7 +41,10 @@ pub fn hash_file(file_path: &str) -> Result<String, io::Error> {
 /// # Returns
 /// * `Ok(String)` containing the hash if successful.
 /// * `Err(io::Error)` if any file operation fails.
-pub fn hash_watch_files(watch_files: &Vec<String>) -> Result<String, io::Error> {
+#[cfg(test)]
+pub fn hash_watch_files(watch_files: &[String]) -> Result<String, io::Error> {
+    use rayon::prelude::*;
+
     if watch_files.is_empty() {
         return Ok(String::new());
     }
@@ -38,17 +54,11 @@ pub fn hash_watch_files(watch_files: &Vec<String>) -> Result<String, io::Error>
 
     let hashes: Vec<String> = sorted_files
         .par_iter()
-        .filter_map(|file_path| match hash_file(file_path) {
-            Ok(hash) => Some(hash),
-            Err(_) => None,
-        })
+        .filter_map(|file_path| hash_file(file_path).ok())
         .collect();
 
     if sorted_files.len() != hashes.len() {
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            "Failed to hash one or more files",
-        ));
+        return Err(io::Error::other("Failed to hash one or more files"));
     }
 
     let combined_hash_string = hashes.join("");
@@ -0,0 +1,123 @@
+use crate::utils::LogFormat;
+use std::io;
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+/// Maps the `-v`/`-q` count to a log level. `0` is the default `INFO` level, every `-v` adds one
+/// level of detail and every `-q` removes one.
+///
+/// # Arguments
+/// * `verbosity` - The number of `-v` flags minus the number of `-q` flags.
+///
+/// # Returns
+/// * `LevelFilter` The most detailed level that should be logged.
+pub fn verbosity_to_level(verbosity: i8) -> LevelFilter {
+    match verbosity {
+        i8::MIN..=-3 => LevelFilter::OFF,
+        -2 => LevelFilter::ERROR,
+        -1 => LevelFilter::WARN,
+        0 => LevelFilter::INFO,
+        1 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    }
+}
+
+/// Installs the global `tracing` subscriber. Logs are written to stderr, dockem's own events and
+/// the build output (the `docker` and `buildkit` targets) use the requested level while
+/// dependencies only log warnings unless `-vv` is used. Closing a phase span logs how long the phase took.
+///
+/// # Arguments
+/// * `verbosity` - The number of `-v` flags minus the number of `-q` flags.
+/// * `format` - Whether to log human readable text or JSON lines.
+pub fn init_logging(verbosity: i8, format: LogFormat) {
+    init_logging_with_writer(verbosity, format, io::stderr);
+}
+
+/// Same as `init_logging`, but writes the logs to the given writer instead of stderr. The CLI uses
+/// this to print log lines above its progress bars.
+///
+/// # Arguments
+/// * `verbosity` - The number of `-v` flags minus the number of `-q` flags.
+/// * `format` - Whether to log human readable text or JSON lines.
+/// * `writer` - Creates the writer each log line is written to.
+pub fn init_logging_with_writer<W>(verbosity: i8, format: LogFormat, writer: W)
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let level = verbosity_to_level(verbosity);
+    let filter = log_targets(verbosity);
+
+    match format {
+        LogFormat::Text => tracing_subscriber::fmt()
+            .with_max_level(level)
+            .with_writer(writer)
+            .with_target(false)
+            .with_span_events(FmtSpan::CLOSE)
+            .finish()
+            .with(filter)
+            .init(),
+        LogFormat::Json => tracing_subscriber::fmt()
+            .json()
+            .with_max_level(level)
+            .with_writer(writer)
+            .with_span_events(FmtSpan::CLOSE)
+            .finish()
+            .with(filter)
+            .init(),
+    }
+}
+
+/// Builds the per-target filter for the verbosity. dockem's own events and the build output of
+/// both backends use the requested level, everything else is capped at `WARN` below `-vv`.
+///
+/// # Arguments
+/// * `verbosity` - The number of `-v` flags minus the number of `-q` flags.
+///
+/// # Returns
+/// * `Targets` The filter to install on the subscriber.
+pub fn log_targets(verbosity: i8) -> Targets {
+    let level = verbosity_to_level(verbosity);
+    let dependency_level = if verbosity >= 2 {
+        level
+    } else {
+        level.min(LevelFilter::WARN)
+    };
+    Targets::new()
+        .with_default(dependency_level)
+        .with_target(env!("CARGO_CRATE_NAME"), level)
+        .with_target("docker", level)
+        .with_target("buildkit", level)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{log_targets, verbosity_to_level};
+    use tracing::Level;
+    use tracing_subscriber::filter::LevelFilter;
+
+    #[test]
+    fn test_verbosity_to_level() {
+        assert_eq!(verbosity_to_level(0), LevelFilter::INFO);
+        assert_eq!(verbosity_to_level(1), LevelFilter::DEBUG);
+        assert_eq!(verbosity_to_level(2), LevelFilter::TRACE);
+        assert_eq!(verbosity_to_level(5), LevelFilter::TRACE);
+        assert_eq!(verbosity_to_level(-1), LevelFilter::WARN);
+        assert_eq!(verbosity_to_level(-2), LevelFilter::ERROR);
+        assert_eq!(verbosity_to_level(-3), LevelFilter::OFF);
+    }
+
+    #[test]
+    fn test_build_output_uses_the_requested_level() {
+        let filter = log_targets(0);
+        assert!(filter.would_enable("docker", &Level::INFO));
+        assert!(filter.would_enable("buildkit", &Level::INFO));
+        assert!(!filter.would_enable("buildkit", &Level::DEBUG));
+        assert!(!filter.would_enable("hyper", &Level::INFO));
+
+        let filter = log_targets(-1);
+        assert!(!filter.would_enable("buildkit", &Level::INFO));
+    }
+}
@@ -0,0 +1,287 @@
+use crate::utils::{is_remote_source, Dockerfile};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+
+/// How serious a lint finding is. Errors make the `lint` command fail, warnings are only reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintSeverity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for LintSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self {
+            LintSeverity::Warning => "warning",
+            LintSeverity::Error => "error",
+        };
+        f.pad(severity)
+    }
+}
+
+/// The problems `lint_dockerfile` looks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LintRule {
+    /// A base image that is not pinned to a digest, so the same Dockerfile can build different
+    /// images over time.
+    UnpinnedBaseImage,
+    /// A base image with the `latest` tag, or without a tag at all.
+    LatestTag,
+    /// An `ADD` that downloads a URL, which is neither cached nor verified.
+    AddUrl,
+    /// The final stage runs as root because it has no `USER`.
+    MissingUser,
+    /// An `apt-get install` that leaves the package lists in the layer.
+    AptGetCleanup,
+    /// A `COPY` or `ADD` source that reaches outside the build context with `..`.
+    CopyOutsideContext,
+}
+
+impl LintRule {
+    /// The severity findings of this rule are reported with.
+    pub fn severity(&self) -> LintSeverity {
+        match self {
+            LintRule::LatestTag | LintRule::CopyOutsideContext => LintSeverity::Error,
+            LintRule::UnpinnedBaseImage
+            | LintRule::AddUrl
+            | LintRule::MissingUser
+            | LintRule::AptGetCleanup => LintSeverity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for LintRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rule = match self {
+            LintRule::UnpinnedBaseImage => "unpinned-base-image",
+            LintRule::LatestTag => "latest-tag",
+            LintRule::AddUrl => "add-url",
+            LintRule::MissingUser => "missing-user",
+            LintRule::AptGetCleanup => "apt-get-cleanup",
+            LintRule::CopyOutsideContext => "copy-outside-context",
+        };
+        f.pad(rule)
+    }
+}
+
+/// A problem found in a Dockerfile.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LintFinding {
+    /// The line of the instruction the problem is on, counting from 1.
+    pub line: usize,
+    pub rule: LintRule,
+    pub severity: LintSeverity,
+    pub message: String,
+}
+
+impl LintFinding {
+    fn new(line: usize, rule: LintRule, message: String) -> Self {
+        Self {
+            line,
+            rule,
+            severity: rule.severity(),
+            message,
+        }
+    }
+}
+
+/// Checks a Dockerfile for common problems, see `LintRule` for what is looked for. Base images
+/// that still contain a build argument after substitution are not checked, as their final value
+/// is only known at build time.
+///
+/// # Arguments
+/// * `dockerfile` - The parsed Dockerfile.
+///
+/// # Returns
+/// * `Vec<LintFinding>` containing every problem, ordered by line.
+pub fn lint_dockerfile(dockerfile: &Dockerfile) -> Vec<LintFinding> {
+    let mut findings = Vec::new();
+
+    for base_image in dockerfile.base_images() {
+        let image = &base_image.image;
+        if image.contains('$') || image.contains('@') {
+            continue;
+        }
+        let name = image.rsplit('/').next().unwrap_or(image);
+        match name.split_once(':') {
+            Some((_, "latest")) | None => findings.push(LintFinding::new(
+                base_image.line,
+                LintRule::LatestTag,
+                format!(
+                    "The base image '{}' uses the latest tag, pin it to a version tag",
+                    image
+                ),
+            )),
+            Some(_) => findings.push(LintFinding::new(
+                base_image.line,
+                LintRule::UnpinnedBaseImage,
+                format!(
+                    "The base image '{}' is not pinned to a digest, use --pin-base-images or add @sha256:…",
+                    image
+                ),
+            )),
+        }
+    }
+
+    for instruction in dockerfile.instructions_named("ADD") {
+        for source in instruction
+            .arguments
+            .split_last()
+            .map(|(_, sources)| sources)
+            .unwrap_or_default()
+        {
+            if is_remote_source(source) {
+                findings.push(LintFinding::new(
+                    instruction.line,
+                    LintRule::AddUrl,
+                    format!(
+                        "ADD downloads '{}', use RUN with curl or wget and verify a checksum instead",
+                        source
+                    ),
+                ));
+            }
+        }
+    }
+
+    for instruction in dockerfile.instructions_named("RUN") {
+        let caches_apt = instruction
+            .flags
+            .iter()
+            .any(|(flag, value)| flag == "mount" && value.contains("/var/lib/apt"));
+        if instruction.raw.contains("apt-get")
+            && instruction.raw.contains("install")
+            && !instruction.raw.contains("/var/lib/apt/lists")
+            && !caches_apt
+        {
+            findings.push(LintFinding::new(
+                instruction.line,
+                LintRule::AptGetCleanup,
+                "apt-get install leaves the package lists in the layer, add && rm -rf /var/lib/apt/lists/*".to_string(),
+            ));
+        }
+    }
+
+    for source in dockerfile.copy_sources() {
+        if source.context_path().is_none() {
+            findings.push(LintFinding::new(
+                source.line,
+                LintRule::CopyOutsideContext,
+                format!(
+                    "The {} source '{}' is outside the build context, Docker cannot read it",
+                    source.keyword, source.source
+                ),
+            ));
+        }
+    }
+
+    findings.extend(missing_user(dockerfile));
+    findings.sort_by_key(|finding| finding.line);
+    findings
+}
+
+/// Finds out which user the final stage runs as, following stages that are built from earlier
+/// stages.
+fn missing_user(dockerfile: &Dockerfile) -> Option<LintFinding> {
+    let mut stage_users: HashMap<String, Option<(usize, String)>> = HashMap::new();
+    let mut current: Option<(usize, Option<(usize, String)>)> = None;
+    let mut current_name: Option<String> = None;
+
+    for instruction in &dockerfile.instructions {
+        match instruction.keyword.as_str() {
+            "FROM" => {
+                if let (Some(name), Some((_, user))) = (current_name.take(), &current) {
+                    stage_users.insert(name, user.clone());
+                }
+                let image = instruction.arguments.first().cloned().unwrap_or_default();
+                let inherited = stage_users
+                    .get(&image.to_lowercase())
+                    .cloned()
+                    .unwrap_or_default();
+                current = Some((instruction.line, inherited));
+                current_name = match instruction.arguments.as_slice() {
+                    [_, keyword, name] if keyword.eq_ignore_ascii_case("as") => {
+                        Some(name.to_lowercase())
+                    }
+                    _ => None,
+                };
+            }
+            "USER" => {
+                if let (Some((_, user)), Some(name)) =
+                    (current.as_mut(), instruction.arguments.first())
+                {
+                    *user = Some((instruction.line, name.clone()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let (from_line, user) = current?;
+    match user {
+        None => Some(LintFinding::new(
+            from_line,
+            LintRule::MissingUser,
+            "The final stage has no USER and runs as root, add a USER for an unprivileged user"
+                .to_string(),
+        )),
+        Some((line, user)) if matches!(user.split(':').next(), Some("root" | "0")) => {
+            Some(LintFinding::new(
+                line,
+                LintRule::MissingUser,
+                "The final stage runs as root, switch to an unprivileged USER".to_string(),
+            ))
+        }
+        Some(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{lint_dockerfile, Dockerfile, LintRule, LintSeverity};
+
+    fn rules(contents: &str) -> Vec<(usize, LintRule)> {
+        lint_dockerfile(&Dockerfile::parse(contents))
+            .into_iter()
+            .map(|finding| (finding.line, finding.rule))
+            .collect()
+    }
+
+    #[test]
+    fn test_common_problems_are_found() {
+        let dockerfile = "FROM node AS build\n\
+                          ADD https://example.com/tool.tgz /tmp/\n\
+                          RUN apt-get update && apt-get install -y curl\n\
+                          COPY ../shared ./shared\n\
+                          FROM nginx:1.27\n\
+                          USER root\n\
+                          COPY --from=build /app /srv\n";
+
+        assert_eq!(
+            rules(dockerfile),
+            vec![
+                (1, LintRule::LatestTag),
+                (2, LintRule::AddUrl),
+                (3, LintRule::AptGetCleanup),
+                (4, LintRule::CopyOutsideContext),
+                (5, LintRule::UnpinnedBaseImage),
+                (6, LintRule::MissingUser),
+            ]
+        );
+        assert_eq!(LintRule::LatestTag.severity(), LintSeverity::Error);
+    }
+
+    #[test]
+    fn test_good_practices_are_not_reported() {
+        let dockerfile = "FROM node:20@sha256:aaa AS base\n\
+                          RUN apt-get update && apt-get install -y curl \\\n\
+                              && rm -rf /var/lib/apt/lists/*\n\
+                          USER node\n\
+                          FROM base\n\
+                          COPY --chown=node src ./src\n";
+
+        assert_eq!(rules(dockerfile), vec![]);
+    }
+}
@@ -1,30 +1,24 @@
-/// Custom macro to conditionally return early or exit based on environment.
+/// Custom macro to return early from a validation function.
 ///
-/// - In tests, it returns an `Ok(())` if the condition is met, or an `Err(String)` otherwise.
-/// - In application mode, it prints the error message and exits with a status code of 1 if the condition is not met.
+/// It returns an `Ok(())` if the condition is met, or an `Err(String)` otherwise. The caller maps
+/// the error to `DockemError::Validation`, which exits the application with its own exit code.
 ///
 /// # Arguments
 /// * `$condition` - The condition to check.
 /// * `$err_msg` - The error message to display or return.
 ///
 /// # Example
-/// ```rust
+/// ```ignore
 /// assert_or_exit!(file_exists, "ERROR: File does not exist.");
 /// ```
-#[macro_export]
 macro_rules! assert_or_exit {
     ($condition:expr, $err_msg:expr) => {{
         if $condition {
             return Ok(());
         }
 
-        if cfg!(test) {
-            // In test mode, return an error
-            return Err($err_msg.to_string());
-        } else {
-            // In application mode, exit with a status code of 1
-            eprintln!("{}", $err_msg);
-            std::process::exit(1);
-        }
+        return Err($err_msg.to_string());
     }};
 }
+
+pub(crate) use assert_or_exit;
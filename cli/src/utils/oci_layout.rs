@@ -0,0 +1,251 @@
+use crate::utils::{compress_layer_to_file, CompressedLayer, DockerArchive};
+use anyhow::{anyhow, Context, Result};
+use oci_client::client::ImageLayer;
+use oci_client::manifest::{
+    ImageIndexEntry, OciDescriptor, OciImageIndex, OciImageManifest, IMAGE_CONFIG_MEDIA_TYPE,
+    OCI_IMAGE_INDEX_MEDIA_TYPE, OCI_IMAGE_MEDIA_TYPE,
+};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// The annotation containerd and BuildKit store the full image name in.
+const IMAGE_NAME_ANNOTATION: &str = "io.containerd.image.name";
+
+/// The annotation the OCI image spec stores the tag of an image in.
+const REF_NAME_ANNOTATION: &str = "org.opencontainers.image.ref.name";
+
+/// A directory in the OCI image layout, as written by `buildctl --output type=oci` and read by
+/// `skopeo copy oci:...`. Images are found by their full name, so one directory can hold every
+/// image a project builds and act as the cache for the next build.
+#[derive(Debug, Clone)]
+pub struct OciLayout {
+    path: PathBuf,
+}
+
+impl OciLayout {
+    /// Uses the given directory, which is created when the first image is added.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Finds an image in the index of the layout.
+    ///
+    /// # Arguments
+    /// * `image_name` - The full image name, e.g. `eu.reg.io/my-org/app:h2-…`.
+    ///
+    /// # Returns
+    /// * `Ok(Some(ImageIndexEntry))` describing the manifest of the image.
+    /// * `Ok(None)` if the layout does not exist or does not contain the image.
+    /// * `Err(anyhow::Error)` if the index cannot be read.
+    pub fn find(&self, image_name: &str) -> Result<Option<ImageIndexEntry>> {
+        Ok(self
+            .index()?
+            .manifests
+            .into_iter()
+            .find(|entry| annotation(entry, IMAGE_NAME_ANNOTATION) == Some(image_name)))
+    }
+
+    /// Writes the blobs and the manifest of an image saved with `docker save`. The image is not
+    /// in the index until it is tagged.
+    ///
+    /// # Arguments
+    /// * `archive` - The saved image.
+    ///
+    /// # Returns
+    /// * `Ok(ImageIndexEntry)` describing the manifest of the image.
+    /// * `Err(anyhow::Error)` if the archive cannot be read or the layout cannot be written.
+    pub fn add_image(&self, archive: &DockerArchive) -> Result<ImageIndexEntry> {
+        fs::create_dir_all(self.path.join("blobs/sha256"))
+            .with_context(|| format!("Unable to create {}", self.path.display()))?;
+        fs::write(
+            self.path.join("oci-layout"),
+            r#"{"imageLayoutVersion":"1.0.0"}"#,
+        )?;
+
+        let mut layers = Vec::with_capacity(archive.layers.len());
+        for name in &archive.layers {
+            let layer = archive.read_with(name, |reader| compress_layer_to_file(reader))?;
+            layers.push(self.write_layer(layer)?);
+        }
+        let config = self.write_blob(archive.read(&archive.config)?, IMAGE_CONFIG_MEDIA_TYPE)?;
+
+        let manifest = OciImageManifest {
+            media_type: Some(OCI_IMAGE_MEDIA_TYPE.to_string()),
+            config,
+            layers,
+            ..Default::default()
+        };
+        let manifest = self.write_blob(serde_json::to_vec(&manifest)?, OCI_IMAGE_MEDIA_TYPE)?;
+        Ok(ImageIndexEntry {
+            media_type: manifest.media_type,
+            digest: manifest.digest,
+            size: manifest.size,
+            platform: None,
+            annotations: None,
+            artifact_type: None,
+        })
+    }
+
+    /// Adds an image to the index under the given names, replacing images with the same names.
+    ///
+    /// # Arguments
+    /// * `entry` - The manifest returned by `add_image` or `find`.
+    /// * `image_names` - The full image names, e.g. `eu.reg.io/my-org/app:v1.0.0`.
+    ///
+    /// # Returns
+    /// * `Ok(())` if the index was written.
+    /// * `Err(anyhow::Error)` if the index cannot be read or written.
+    pub fn tag(&self, entry: &ImageIndexEntry, image_names: &[String]) -> Result<()> {
+        let mut index = self.index()?;
+        index.manifests.retain(|existing| {
+            annotation(existing, IMAGE_NAME_ANNOTATION)
+                .is_none_or(|name| !image_names.iter().any(|image_name| image_name == name))
+        });
+        for image_name in image_names {
+            let tag = image_name
+                .rsplit_once(':')
+                .filter(|(_, tag)| !tag.contains('/'))
+                .map_or(image_name.as_str(), |(_, tag)| tag);
+            index.manifests.push(ImageIndexEntry {
+                annotations: Some(BTreeMap::from([
+                    (IMAGE_NAME_ANNOTATION.to_string(), image_name.clone()),
+                    (REF_NAME_ANNOTATION.to_string(), tag.to_string()),
+                ])),
+                ..entry.clone()
+            });
+        }
+
+        let partial = self.path.join("index.json.partial");
+        fs::write(&partial, serde_json::to_vec_pretty(&index)?)?;
+        fs::rename(&partial, self.path.join("index.json"))
+            .with_context(|| format!("Unable to write the index of {}", self.path.display()))
+    }
+
+    /// Reads `index.json`, an empty index is returned for a layout that does not exist yet.
+    fn index(&self) -> Result<OciImageIndex> {
+        let path = self.path.join("index.json");
+        match fs::read(&path) {
+            Ok(index) => serde_json::from_slice(&index)
+                .with_context(|| format!("Invalid OCI index {}", path.display())),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(OciImageIndex {
+                schema_version: 2,
+                media_type: Some(OCI_IMAGE_INDEX_MEDIA_TYPE.to_string()),
+                manifests: Vec::new(),
+                annotations: None,
+                artifact_type: None,
+            }),
+            Err(error) => Err(anyhow!("Unable to read {}: {}", path.display(), error)),
+        }
+    }
+
+    /// Writes a blob under its digest, blobs that are already in the layout are kept.
+    fn write_blob(&self, data: Vec<u8>, media_type: &str) -> Result<OciDescriptor> {
+        let layer = ImageLayer::new(data, media_type.to_string(), None);
+        let digest = layer.sha256_digest();
+        let path = blob_path(&self.path, &digest);
+        if !path.exists() {
+            debug!("Writing the blob {}", digest);
+            fs::write(&path, &layer.data)
+                .with_context(|| format!("Unable to write {}", path.display()))?;
+        }
+        Ok(OciDescriptor {
+            media_type: layer.media_type,
+            digest,
+            size: layer.data.len() as i64,
+            ..Default::default()
+        })
+    }
+
+    /// Copies a compressed layer into the layout under its digest, like `write_blob` without
+    /// holding the layer in memory.
+    fn write_layer(&self, layer: CompressedLayer) -> Result<OciDescriptor> {
+        let path = blob_path(&self.path, &layer.digest);
+        if !path.exists() {
+            debug!("Writing the blob {}", layer.digest);
+            io::copy(&mut File::open(&layer.path)?, &mut File::create(&path)?)
+                .with_context(|| format!("Unable to write {}", path.display()))?;
+        }
+        Ok(OciDescriptor {
+            media_type: layer.media_type.to_string(),
+            digest: layer.digest,
+            size: layer.size as i64,
+            ..Default::default()
+        })
+    }
+}
+
+/// Returns the path of a blob in the layout, e.g. `blobs/sha256/<hex>` for `sha256:<hex>`.
+fn blob_path(layout: &Path, digest: &str) -> PathBuf {
+    let (algorithm, hex) = digest.split_once(':').unwrap_or(("sha256", digest));
+    layout.join("blobs").join(algorithm).join(hex)
+}
+
+/// Returns the value of an annotation of an index entry.
+fn annotation<'a>(entry: &'a ImageIndexEntry, key: &str) -> Option<&'a str> {
+    entry
+        .annotations
+        .as_ref()
+        .and_then(|annotations| annotations.get(key))
+        .map(String::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{DockerArchive, OciLayout};
+    use std::fs::{self, File};
+    use tar::{Builder, Header};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_images_are_found_by_their_name() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let path = temp_dir.path().join("image.tar");
+        let mut builder = Builder::new(File::create(&path).unwrap());
+        for (name, data) in [
+            (
+                "manifest.json",
+                br#"[{"Config":"config.json","Layers":["layer.tar"]}]"#.as_slice(),
+            ),
+            ("config.json", b"{}"),
+            ("layer.tar", b"layer contents"),
+        ] {
+            let mut header = Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, data).unwrap();
+        }
+        builder.finish().unwrap();
+        drop(builder);
+
+        let layout = OciLayout::new(temp_dir.path().join("out"));
+        assert!(layout.find("eu.reg.io/app:h2-abc").unwrap().is_none());
+        let entry = layout
+            .add_image(&DockerArchive::open(&path).unwrap())
+            .unwrap();
+        let names = vec![
+            "eu.reg.io/app:h2-abc".to_string(),
+            "eu.reg.io/app:v1".to_string(),
+        ];
+        layout.tag(&entry, &names).unwrap();
+        layout.tag(&entry, &names[1..]).unwrap();
+
+        let found = layout.find("eu.reg.io/app:h2-abc").unwrap().unwrap();
+        assert_eq!(found.digest, entry.digest);
+        let index = fs::read_to_string(temp_dir.path().join("out/index.json")).unwrap();
+        assert_eq!(
+            index
+                .matches(r#""org.opencontainers.image.ref.name": "v1""#)
+                .count(),
+            1
+        );
+        assert!(temp_dir
+            .path()
+            .join("out/blobs/sha256")
+            .join(entry.digest.trim_start_matches("sha256:"))
+            .exists());
+    }
+}
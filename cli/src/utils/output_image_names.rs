@@ -0,0 +1,113 @@
+use crate::utils::{generate_docker_image_name, BuildDockerImageParams};
+use tracing::{info, warn};
+
+/// Returns the full image names an image is published under besides its hashed name: every tag
+/// with the version appended, `latest` with `params.latest` and the bare version with
+/// `params.main_version`. Without any of these the bare version is used.
+///
+/// # Arguments
+/// * `params` - Parameters with the registry, image name and tagging options.
+/// * `version` - The version appended to the tags.
+///
+/// # Returns
+/// * `Vec<String>` containing the image names in the order they are pushed.
+pub fn output_image_names(params: &BuildDockerImageParams, version: &str) -> Vec<String> {
+    let image_name =
+        |tag: &str| generate_docker_image_name(&params.registry, &params.image_name, tag);
+
+    // Versioned tags (e.g., `tag-version`)
+    let mut image_names: Vec<String> = params
+        .tag
+        .iter()
+        .map(|tag| image_name(&format!("{}-{}", tag, version)))
+        .collect();
+
+    // If no tags are specified and neither `latest` nor `main_version` is set,
+    // publish the main version (e.g., `image-name:version`)
+    if params.tag.is_empty() && !params.latest && !params.main_version {
+        let main_version_image_name = image_name(version);
+        warn!(
+            "No tags were specified and you have not selected the --latest flag, \
+            so the image will be deployed to the main version: {}",
+            main_version_image_name
+        );
+        image_names.push(main_version_image_name);
+    }
+
+    if params.latest {
+        let latest_image_name = image_name("latest");
+        info!(
+            "You have selected the --latest flag, so the image will be deployed to the latest tag: {}",
+            latest_image_name
+        );
+        image_names.push(latest_image_name);
+    }
+
+    if params.main_version {
+        let main_version_image_name = image_name(version);
+        info!(
+            "You have selected the --main-version flag, so the image will be deployed to the main version: {}",
+            main_version_image_name
+        );
+        image_names.push(main_version_image_name);
+    }
+
+    image_names
+}
+
+/// Returns the full image name whose current image a new build replaces, which is what the build
+/// is compared with to explain a rebuild: the `latest` tag with `params.latest`, otherwise the
+/// first of the `output_image_names`.
+///
+/// # Arguments
+/// * `params` - Parameters with the registry, image name and tagging options.
+/// * `version` - The version appended to the tags.
+pub fn previous_image_name(params: &BuildDockerImageParams, version: &str) -> String {
+    let tag = if params.latest {
+        "latest".to_string()
+    } else {
+        match params.tag.first() {
+            Some(tag) => format!("{}-{}", tag, version),
+            None => version.to_string(),
+        }
+    };
+    generate_docker_image_name(&params.registry, &params.image_name, &tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{output_image_names, previous_image_name, BuildDockerImageParams};
+
+    #[test]
+    fn test_output_image_names_follow_the_flags() {
+        let params = BuildDockerImageParams::builder("my-org/app")
+            .registry("eu.reg.io")
+            .tag("dev")
+            .latest(true)
+            .build()
+            .unwrap();
+        assert_eq!(
+            output_image_names(&params, "v1.2.3"),
+            vec![
+                "eu.reg.io/my-org/app:dev-v1.2.3",
+                "eu.reg.io/my-org/app:latest"
+            ]
+        );
+        assert_eq!(
+            previous_image_name(&params, "v1.2.3"),
+            "eu.reg.io/my-org/app:latest"
+        );
+
+        let params = BuildDockerImageParams::builder("my-org/app")
+            .build()
+            .unwrap();
+        assert_eq!(
+            output_image_names(&params, "v1.2.3"),
+            vec!["docker.io/my-org/app:v1.2.3"]
+        );
+        assert_eq!(
+            previous_image_name(&params, "v1.2.3"),
+            "docker.io/my-org/app:v1.2.3"
+        );
+    }
+}
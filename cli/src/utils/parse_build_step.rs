@@ -0,0 +1,60 @@
+/// Parses the `Step N/M : INSTRUCTION` lines written by the Docker build stream, the
+/// `STEP N/M: INSTRUCTION` lines Podman writes instead and the `#7 [stage N/M] INSTRUCTION`
+/// lines of BuildKit's plain progress output.
+///
+/// # Arguments
+/// * `message` - A message from the Docker build stream.
+///
+/// # Returns
+/// * `Some((step, total, instruction))` if the message starts a new build step.
+/// * `None` for any other output.
+pub fn parse_build_step(message: &str) -> Option<(u32, u32, String)> {
+    let message = message.trim_start();
+    let (progress, instruction) = if let Some(rest) = message.strip_prefix("Step ") {
+        rest.split_once(" : ")?
+    } else if let Some(rest) = message.strip_prefix("STEP ") {
+        rest.split_once(": ")?
+    } else {
+        let (_, rest) = message.strip_prefix('#')?.split_once(" [")?;
+        let (stage, instruction) = rest.split_once("] ")?;
+        (stage.rsplit(' ').next()?, instruction)
+    };
+    let (step, total) = progress.split_once('/')?;
+    Some((
+        step.trim().parse().ok()?,
+        total.trim().parse().ok()?,
+        instruction.trim_end().to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::parse_build_step;
+
+    #[test]
+    fn test_parse_build_step() {
+        assert_eq!(
+            parse_build_step("Step 3/7 : RUN npm ci\n"),
+            Some((3, 7, "RUN npm ci".to_string()))
+        );
+        assert_eq!(
+            parse_build_step("STEP 2/5: COPY package.json ./\n"),
+            Some((2, 5, "COPY package.json ./".to_string()))
+        );
+        assert_eq!(
+            parse_build_step("#7 [build 4/6] RUN npm ci"),
+            Some((4, 6, "RUN npm ci".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_other_output_is_ignored() {
+        assert_eq!(parse_build_step(" ---> Using cache\n"), None);
+        assert_eq!(parse_build_step("Step one : RUN npm ci"), None);
+        assert_eq!(
+            parse_build_step("#1 [internal] load build definition from Dockerfile"),
+            None
+        );
+        assert_eq!(parse_build_step("Successfully built 0123456789ab\n"), None);
+    }
+}
@@ -0,0 +1,116 @@
+use crate::utils::{Dockerfile, ResolvedBaseImage};
+use tracing::{debug, warn};
+
+/// Rewrites the `FROM` lines of a Dockerfile to pin every base image to the digest it was
+/// resolved to, e.g. `FROM node:20-alpine` becomes `FROM node:20-alpine@sha256:…`. Images that
+/// are already pinned, `scratch` and earlier stages are left as they are. Everything else in the
+/// Dockerfile, including comments and line numbers, is kept.
+///
+/// # Arguments
+/// * `contents` - The contents of the Dockerfile.
+/// * `base_images` - The base images and their digests, see `resolve_base_images`.
+///
+/// # Returns
+/// * `String` the Dockerfile with the base images pinned.
+pub fn pin_base_images(contents: &str, base_images: &[ResolvedBaseImage]) -> String {
+    let dockerfile = Dockerfile::parse(contents);
+    let mut lines: Vec<String> = contents.lines().map(String::from).collect();
+
+    for base_image in dockerfile.base_images() {
+        let Some(resolved) = base_images
+            .iter()
+            .find(|resolved| resolved.image == base_image.image)
+        else {
+            continue;
+        };
+        if resolved.image.contains('@') {
+            continue;
+        }
+        let written = dockerfile
+            .instructions
+            .iter()
+            .find(|instruction| instruction.line == base_image.line)
+            .and_then(|instruction| instruction.arguments.first());
+        let Some(written) = written else {
+            continue;
+        };
+
+        let pinned = format!("{}@{}", resolved.image, resolved.digest);
+        match lines.get_mut(base_image.line - 1) {
+            Some(line) if contains_token(line, written) => {
+                *line = replace_token(line, written, &pinned);
+                debug!("Pinned line {} to {}", base_image.line, pinned);
+            }
+            _ => warn!(
+                "Unable to pin the base image {} on line {}, it is built unpinned",
+                resolved.image, base_image.line
+            ),
+        }
+    }
+
+    let mut pinned = lines.join("\n");
+    if contents.ends_with('\n') {
+        pinned.push('\n');
+    }
+    pinned
+}
+
+fn contains_token(line: &str, token: &str) -> bool {
+    line.split_whitespace().any(|word| word == token)
+}
+
+/// Replaces the first whitespace separated word that equals the token.
+fn replace_token(line: &str, token: &str, replacement: &str) -> String {
+    let mut offset = 0;
+    for word in line.split_whitespace() {
+        let start = offset + line[offset..].find(word).unwrap_or(0);
+        offset = start + word.len();
+        if word == token {
+            return format!("{}{}{}", &line[..start], replacement, &line[offset..]);
+        }
+    }
+    line.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{pin_base_images, ResolvedBaseImage};
+
+    fn resolved(image: &str, digest: &str) -> ResolvedBaseImage {
+        ResolvedBaseImage {
+            image: image.to_string(),
+            reference: format!("docker.io/library/{}", image),
+            digest: digest.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_from_lines_are_pinned_to_the_resolved_digests() {
+        let dockerfile = "ARG NODE=20\n\
+                          # Build stage\n\
+                          FROM --platform=$BUILDPLATFORM node:${NODE} AS build\n\
+                          RUN npm ci\n\
+                          FROM build AS test\n\
+                          FROM nginx:1.27\n\
+                          COPY --from=build /app /srv\n";
+
+        let pinned = pin_base_images(
+            dockerfile,
+            &[
+                resolved("node:20", "sha256:aaa"),
+                resolved("nginx:1.27", "sha256:bbb"),
+            ],
+        );
+
+        assert_eq!(
+            pinned,
+            "ARG NODE=20\n\
+             # Build stage\n\
+             FROM --platform=$BUILDPLATFORM node:20@sha256:aaa AS build\n\
+             RUN npm ci\n\
+             FROM build AS test\n\
+             FROM nginx:1.27@sha256:bbb\n\
+             COPY --from=build /app /srv\n"
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use crate::utils::BuildDockerImageParams;
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Resolves the project root that paths are hashed relative to: the one that was given, or the
+/// closest git repository around the build directory. Outside a git repository it is the closest
+/// directory that contains the build directory, the Dockerfile and every watch, so the root never
+/// depends on the working directory.
+///
+/// # Arguments
+/// * `params` - The build parameters that name the project root and the inputs to hash.
+///
+/// # Returns
+/// * `Ok(PathBuf)` containing the canonical path of the project root.
+/// * `Err(anyhow::Error)` if the project root or the build directory could not be resolved.
+pub fn resolve_project_root(params: &BuildDockerImageParams) -> Result<PathBuf> {
+    if let Some(root) = &params.project_root {
+        return fs::canonicalize(root)
+            .with_context(|| format!("Unable to resolve the project root {:?}", root));
+    }
+
+    let directory = fs::canonicalize(&params.directory).with_context(|| {
+        format!(
+            "Unable to resolve the build directory '{}'",
+            params.directory
+        )
+    })?;
+    if let Some(root) = git_root(&directory) {
+        return Ok(root.to_path_buf());
+    }
+
+    // Files are resolved through their parent like in `project_relative_path`, inputs that do
+    // not exist are reported when they are hashed
+    let file_parents = std::iter::once(&params.dockerfile_path)
+        .chain(params.watch_file.iter().flatten())
+        .map(|file| match Path::new(file).parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        });
+    let watch_directories = params.watch_directory.iter().flatten().map(Path::new);
+    let root = file_parents
+        .chain(watch_directories)
+        .filter_map(|path| fs::canonicalize(path).ok())
+        .fold(directory, |root, path| {
+            root.ancestors()
+                .find(|ancestor| path.starts_with(ancestor))
+                .unwrap_or(&root)
+                .to_path_buf()
+        });
+    Ok(root)
+}
+
+/// Returns the closest ancestor of the canonical directory that contains a `.git` entry.
+fn git_root(directory: &Path) -> Option<&Path> {
+    directory
+        .ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())
+}
+
+/// Turns a path into the form it is hashed with: relative to the project root, with `/`
+/// separators and without `.` or `..` components, so that `./Dockerfile`, `Dockerfile` and an
+/// absolute path to the same file all hash the same way. The project root itself is `.`.
+///
+/// # Arguments
+/// * `root` - The canonical path of the project root.
+/// * `path` - The path to normalise, relative to the working directory or absolute.
+///
+/// # Returns
+/// * `Ok(String)` containing the normalised path.
+/// * `Err(anyhow::Error)` if the path does not exist or is outside the project root.
+pub fn project_relative_path(root: &Path, path: &str) -> Result<String> {
+    let as_passed = Path::new(path);
+    // Only the parent is resolved so that a symlinked file keeps its own name
+    let absolute = match (as_passed.parent(), as_passed.file_name()) {
+        (Some(parent), Some(name)) => {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            fs::canonicalize(parent).map(|parent| parent.join(name))
+        }
+        _ => fs::canonicalize(as_passed),
+    }
+    .with_context(|| format!("Unable to resolve the path '{}'", path))?;
+
+    let relative = absolute.strip_prefix(root).map_err(|_| {
+        anyhow!(
+            "The path '{}' is outside the project root '{}', set the project root to a directory that contains it",
+            path,
+            root.display()
+        )
+    })?;
+
+    let components = relative
+        .components()
+        .map(|component| match component {
+            Component::Normal(name) => name
+                .to_str()
+                .ok_or_else(|| anyhow!("The path '{}' is not valid UTF-8", path)),
+            _ => Err(anyhow!("Unable to normalise the path '{}'", path)),
+        })
+        .collect::<Result<Vec<&str>>>()?;
+
+    if components.is_empty() {
+        Ok(".".to_string())
+    } else {
+        Ok(components.join("/"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{project_relative_path, resolve_project_root, BuildDockerImageParams};
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn params_for(directory: &Path) -> BuildDockerImageParams {
+        BuildDockerImageParams::builder("my-org/backend")
+            .directory(directory.to_str().unwrap())
+            .dockerfile_path(directory.join("Dockerfile").to_str().unwrap())
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_project_root_is_the_closest_git_repository() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let root = fs::canonicalize(temp_dir.path()).unwrap();
+        fs::create_dir_all(root.join("repo/.git")).unwrap();
+        fs::create_dir_all(root.join("repo/apps/backend")).unwrap();
+
+        assert_eq!(
+            resolve_project_root(&params_for(&root.join("repo/apps/backend"))).unwrap(),
+            root.join("repo")
+        );
+    }
+
+    #[test]
+    fn test_project_root_outside_git_contains_every_input() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let root = fs::canonicalize(temp_dir.path()).unwrap();
+        fs::create_dir_all(root.join("project/apps/backend")).unwrap();
+        fs::create_dir_all(root.join("project/libs/shared")).unwrap();
+
+        let backend = root.join("project/apps/backend");
+        assert_eq!(
+            resolve_project_root(&params_for(&backend)).unwrap(),
+            backend
+        );
+
+        let mut params = params_for(&backend);
+        params.watch_directory = Some(vec![root
+            .join("project/libs/shared")
+            .to_str()
+            .unwrap()
+            .to_string()]);
+        assert_eq!(resolve_project_root(&params).unwrap(), root.join("project"));
+    }
+
+    #[test]
+    fn test_spellings_of_the_same_path_are_normalised() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let root = fs::canonicalize(temp_dir.path()).unwrap();
+        fs::create_dir_all(root.join("apps/backend")).unwrap();
+        fs::write(root.join("apps/backend/Dockerfile"), "FROM alpine").unwrap();
+
+        let spellings = [
+            root.join("apps/backend/Dockerfile"),
+            root.join("apps/./backend/../backend/Dockerfile"),
+        ];
+        for spelling in spellings {
+            assert_eq!(
+                project_relative_path(&root, spelling.to_str().unwrap()).unwrap(),
+                "apps/backend/Dockerfile"
+            );
+        }
+        assert_eq!(
+            project_relative_path(&root, &format!("{}/", root.display())).unwrap(),
+            "."
+        );
+        assert!(project_relative_path(&root.join("apps"), "/").is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use crate::utils::{
+    check_blob_head, compress_layer_to_file, registry_auth_from_credentials, BuildEvent,
+    DockerArchive, EventEmitter,
+};
+use anyhow::{Context, Result};
+use bollard::auth::DockerCredentials;
+use bytes::Bytes;
+use futures_util::stream::{self, Stream};
+use oci_client::client::{Client as RegistryClient, ImageLayer};
+use oci_client::errors::OciDistributionError;
+use oci_client::manifest::{
+    OciDescriptor, OciImageManifest, IMAGE_CONFIG_MEDIA_TYPE, OCI_IMAGE_MEDIA_TYPE,
+};
+use oci_client::{Reference, RegistryOperation};
+use std::str::FromStr;
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+use tokio::task;
+use tracing::{debug, info};
+
+/// The size of the chunks a layer is uploaded in, the most the registry client sends at once.
+const UPLOAD_CHUNK_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Pushes an image saved with `docker save` to the registry without the Docker daemon. The
+/// blobs are uploaded and the manifest is written for the target, further tags can then be
+/// copied on the registry with `copy_existing_image_tag`. Uncompressed layers are gzipped into a
+/// temporary file and uploaded from it in chunks, so at most one chunk of a layer is held in
+/// memory. Blobs the repository already has, like unchanged base layers, are not uploaded again. A `BuildEvent::PushProgress` is emitted after every layer and a
+/// `BuildEvent::TagPushed` once pushed.
+///
+/// # Arguments
+/// * `archive` - The saved image.
+/// * `target` - The full image name to push to.
+/// * `registry_client` - The OCI registry client.
+/// * `credentials` - The credentials the Docker daemon would push with.
+/// * `events` - Receives the push progress.
+///
+/// # Returns
+/// * `Ok(())` if the image was pushed.
+/// * `Err(anyhow::Error)` if reading the archive or pushing failed.
+pub async fn push_docker_archive(
+    archive: &DockerArchive,
+    target: &str,
+    registry_client: &RegistryClient,
+    credentials: &DockerCredentials,
+    events: &EventEmitter,
+) -> Result<()> {
+    let reference = Reference::from_str(target)
+        .with_context(|| format!("The image name '{}' is not a valid reference", target))?;
+    registry_client
+        .auth(
+            &reference,
+            &registry_auth_from_credentials(credentials)?,
+            RegistryOperation::Push,
+        )
+        .await
+        .with_context(|| format!("Failed to authenticate to push {}", reference))?;
+
+    let mut layers = Vec::with_capacity(archive.layers.len());
+    let mut uploaded = 0;
+    for name in &archive.layers {
+        let layer = task::spawn_blocking({
+            let archive = archive.clone();
+            let name = name.clone();
+            move || archive.read_with(&name, |reader| compress_layer_to_file(reader))
+        })
+        .await??;
+
+        if check_blob_head(&reference, &layer.digest, registry_client).await {
+            debug!(
+                "The layer {} is already in the repository as {}",
+                name, layer.digest
+            );
+        } else {
+            debug!("Uploading the layer {} as {}", name, layer.digest);
+            let file = File::open(&layer.path).await?;
+            registry_client
+                .push_blob_stream(
+                    &reference,
+                    file_chunks(file),
+                    &layer.digest,
+                    Some(layer.size),
+                )
+                .await
+                .with_context(|| format!("Failed to upload the layer {}", layer.digest))?;
+        }
+
+        uploaded += layer.size;
+        events.emit(BuildEvent::PushProgress {
+            image: target.to_string(),
+            current: uploaded,
+            total: None,
+        });
+        layers.push(OciDescriptor {
+            media_type: layer.media_type.to_string(),
+            digest: layer.digest,
+            size: layer.size as i64,
+            ..Default::default()
+        });
+    }
+
+    let config = task::spawn_blocking({
+        let archive = archive.clone();
+        move || archive.read(&archive.config)
+    })
+    .await??;
+    let config = ImageLayer::new(config, IMAGE_CONFIG_MEDIA_TYPE.to_string(), None);
+    let config_digest = config.sha256_digest();
+    if !check_blob_head(&reference, &config_digest, registry_client).await {
+        registry_client
+            .push_blob(&reference, config.data.clone(), &config_digest)
+            .await
+            .context("Failed to upload the image config")?;
+    }
+
+    let manifest = OciImageManifest {
+        media_type: Some(OCI_IMAGE_MEDIA_TYPE.to_string()),
+        config: OciDescriptor {
+            media_type: config.media_type,
+            digest: config_digest,
+            size: config.data.len() as i64,
+            ..Default::default()
+        },
+        layers,
+        ..Default::default()
+    }
+    .into();
+    registry_client
+        .push_manifest(&reference, &manifest)
+        .await
+        .with_context(|| format!("Failed to push the manifest of {}", reference))?;
+    info!("Pushed the image to {}", reference);
+    events.emit(BuildEvent::TagPushed {
+        image: target.to_string(),
+    });
+    Ok(())
+}
+
+/// Reads a file as a stream of chunks for `push_blob_stream`.
+fn file_chunks(file: File) -> impl Stream<Item = Result<Bytes, OciDistributionError>> {
+    stream::try_unfold(file, |mut file| async move {
+        let mut chunk = Vec::with_capacity(UPLOAD_CHUNK_SIZE as usize);
+        (&mut file)
+            .take(UPLOAD_CHUNK_SIZE)
+            .read_to_end(&mut chunk)
+            .await?;
+        Ok((!chunk.is_empty()).then(|| (Bytes::from(chunk), file)))
+    })
+}
@@ -0,0 +1,62 @@
+use crate::utils::{HashManifest, HASH_MANIFEST_MEDIA_TYPE};
+use anyhow::{Context, Result};
+use oci_client::client::{Client as RegistryClient, Config, ImageLayer};
+use oci_client::manifest::OciImageManifest;
+use oci_client::secrets::RegistryAuth;
+use oci_client::Reference;
+use tracing::info;
+
+/// The media type of the empty config used for OCI artifacts.
+const EMPTY_CONFIG_MEDIA_TYPE: &str = "application/vnd.oci.empty.v1+json";
+
+/// Pushes the hash manifest as a small OCI artifact attached to the hashed image. It is tagged
+/// with `HashManifest::tag_for` the digest of the image, so whichever tag the image is published
+/// under later, the manifest can be found from it.
+///
+/// # Arguments
+/// * `manifest` - The hash manifest of the image that was just built.
+/// * `reference` - The reference of the hashed image that was just pushed.
+/// * `registry_client` - An authenticated instance of the OCI registry client.
+///
+/// # Returns
+/// * `Ok(())` if the artifact was pushed.
+/// * `Err(anyhow::Error)` if serializing or pushing the artifact failed.
+pub async fn push_hash_manifest(
+    manifest: &HashManifest,
+    reference: &Reference,
+    registry_client: &RegistryClient,
+) -> Result<()> {
+    let data = serde_json::to_vec(manifest).context("Failed to serialize the hash manifest")?;
+    let layers = [ImageLayer::new(
+        data,
+        HASH_MANIFEST_MEDIA_TYPE.to_string(),
+        None,
+    )];
+    let config = Config::new(b"{}".to_vec(), EMPTY_CONFIG_MEDIA_TYPE.to_string(), None);
+    let mut oci_manifest = OciImageManifest::build(&layers, &config, None);
+    oci_manifest.artifact_type = Some(HASH_MANIFEST_MEDIA_TYPE.to_string());
+
+    let image_digest = registry_client
+        .fetch_manifest_digest(reference, &RegistryAuth::Anonymous)
+        .await
+        .with_context(|| format!("Failed to resolve the digest of {}", reference))?;
+    let hash_tag = HashManifest::tag_for(&image_digest);
+    let target = Reference::with_tag(
+        reference.registry().to_string(),
+        reference.repository().to_string(),
+        hash_tag.clone(),
+    );
+    registry_client
+        .push(
+            &target,
+            &layers,
+            config,
+            &RegistryAuth::Anonymous,
+            Some(oci_manifest),
+        )
+        .await
+        .with_context(|| format!("Failed to push the hash manifest to {}", target))?;
+
+    info!("Pushed the hash manifest to the tag {}.", hash_tag);
+    Ok(())
+}
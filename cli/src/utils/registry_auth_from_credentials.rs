@@ -0,0 +1,53 @@
+use anyhow::{anyhow, Result};
+use bollard::auth::DockerCredentials;
+use oci_client::secrets::RegistryAuth;
+use openssl::base64;
+
+/// Converts the credentials of the Docker daemon to credentials for the registry client. Logins
+/// from the Docker config only have the base64 encoded `auth` field.
+///
+/// # Arguments
+/// * `credentials` - The credentials from `create_docker_client`.
+///
+/// # Returns
+/// * `Ok(RegistryAuth)` containing basic credentials, or anonymous ones without a login.
+/// * `Err(anyhow::Error)` if the `auth` field is not a base64 encoded `username:password`.
+pub fn registry_auth_from_credentials(credentials: &DockerCredentials) -> Result<RegistryAuth> {
+    if let (Some(username), Some(password)) = (&credentials.username, &credentials.password) {
+        return Ok(RegistryAuth::Basic(username.clone(), password.clone()));
+    }
+    let Some(auth) = credentials.auth.as_deref().filter(|auth| !auth.is_empty()) else {
+        return Ok(RegistryAuth::Anonymous);
+    };
+    let decoded = String::from_utf8(base64::decode_block(auth)?)?;
+    let (username, password) = decoded
+        .split_once(':')
+        .ok_or_else(|| anyhow!("The registry login in the Docker config is invalid"))?;
+    Ok(RegistryAuth::Basic(
+        username.to_string(),
+        password.to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::registry_auth_from_credentials;
+    use bollard::auth::DockerCredentials;
+    use oci_client::secrets::RegistryAuth;
+
+    #[test]
+    fn test_docker_config_logins_are_decoded() {
+        let from_config = DockerCredentials {
+            auth: Some("dXNlcjpwYXNz".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            registry_auth_from_credentials(&from_config).unwrap(),
+            RegistryAuth::Basic(username, password) if username == "user" && password == "pass"
+        ));
+        assert!(matches!(
+            registry_auth_from_credentials(&DockerCredentials::default()).unwrap(),
+            RegistryAuth::Anonymous
+        ));
+    }
+}
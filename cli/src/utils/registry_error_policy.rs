@@ -0,0 +1,36 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Decides what happens when the registry cannot tell us whether the hashed image exists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RegistryErrorPolicy {
+    /// Stop the run and report the registry error.
+    #[default]
+    Abort,
+    /// Treat the image as missing and fall back to building and pushing it.
+    Build,
+}
+
+impl FromStr for RegistryErrorPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "abort" => Ok(RegistryErrorPolicy::Abort),
+            "build" => Ok(RegistryErrorPolicy::Build),
+            _ => Err(format!(
+                "Unknown registry error policy '{}', expected 'abort' or 'build'",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for RegistryErrorPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryErrorPolicy::Abort => write!(f, "abort"),
+            RegistryErrorPolicy::Build => write!(f, "build"),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use crate::utils::{create_registry_client, BuildDockerImageParams, DockemError, Dockerfile};
+use futures_util::future::try_join_all;
+use oci_client::client::Client;
+use oci_client::secrets::RegistryAuth;
+use oci_client::Reference;
+use serde::Serialize;
+use std::str::FromStr;
+use tracing::{debug, info};
+
+/// A base image of the Dockerfile together with the digest its tag currently points at.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ResolvedBaseImage {
+    /// The image as it is written in the `FROM` line, with build arguments substituted.
+    pub image: String,
+    /// The fully qualified reference, e.g. `docker.io/library/node:20-alpine`.
+    pub reference: String,
+    /// The manifest digest, e.g. `sha256:…`.
+    pub digest: String,
+}
+
+/// Resolves every base image in the Dockerfile to the digest of its manifest, so that a new
+/// upstream image under the same tag changes the image hash. Images that are already pinned to
+/// a digest are not looked up. Nothing is resolved unless `params.track_base_images` or
+/// `params.pin_base_images` is set.
+///
+/// Base images on `params.registry` are resolved with the configured credentials, all other
+/// registries are asked anonymously.
+///
+/// # Arguments
+/// * `params` - The build parameters naming the Dockerfile and the registry credentials.
+///
+/// # Returns
+/// * `Ok(Vec<ResolvedBaseImage>)` containing every base image once, in Dockerfile order.
+/// * `Err(DockemError)` if the Dockerfile cannot be read, an image name is invalid or a
+///   registry cannot be asked.
+pub async fn resolve_base_images(
+    params: &BuildDockerImageParams,
+) -> Result<Vec<ResolvedBaseImage>, DockemError> {
+    if !params.track_base_images && !params.pin_base_images {
+        return Ok(Vec::new());
+    }
+
+    let dockerfile = Dockerfile::read(&params.dockerfile_path).map_err(DockemError::Hash)?;
+    let mut images: Vec<String> = Vec::new();
+    for base_image in dockerfile.base_images() {
+        if !images.contains(&base_image.image) {
+            images.push(base_image.image);
+        }
+    }
+
+    let client = create_registry_client(params)?;
+    try_join_all(
+        images
+            .into_iter()
+            .map(|image| resolve_base_image(&client, params, image)),
+    )
+    .await
+}
+
+async fn resolve_base_image(
+    client: &Client,
+    params: &BuildDockerImageParams,
+    image: String,
+) -> Result<ResolvedBaseImage, DockemError> {
+    let reference = Reference::from_str(&image).map_err(|error| {
+        DockemError::Validation(format!(
+            "The base image '{}' is not a valid image reference: {}",
+            image, error
+        ))
+    })?;
+
+    let digest = match reference.digest() {
+        Some(digest) => digest.to_string(),
+        None => {
+            let auth = match (&params.docker_username, &params.docker_password) {
+                (Some(username), Some(password)) if reference.registry() == params.registry => {
+                    RegistryAuth::Basic(username.clone(), password.clone())
+                }
+                _ => RegistryAuth::Anonymous,
+            };
+            debug!("Resolving the base image {}", reference.whole());
+            client
+                .fetch_manifest_digest(&reference, &auth)
+                .await
+                .map_err(|error| {
+                    let context = format!("Unable to resolve the base image '{}'", image);
+                    match DockemError::from_registry_error(error) {
+                        DockemError::RegistryAuth(error) => {
+                            DockemError::RegistryAuth(error.context(context))
+                        }
+                        DockemError::RegistryIo(error) => {
+                            DockemError::RegistryIo(error.context(context))
+                        }
+                        error => error,
+                    }
+                })?
+        }
+    };
+
+    info!("Resolved the base image {} to {}", image, digest);
+    Ok(ResolvedBaseImage {
+        image,
+        reference: reference.whole(),
+        digest,
+    })
+}
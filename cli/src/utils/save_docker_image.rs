@@ -0,0 +1,89 @@
+use anyhow::{Context, Result};
+use bollard::Docker;
+use futures_util::stream::StreamExt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use tempfile::TempPath;
+use tracing::info;
+
+/// Creates an empty file with a unique name in the temporary directory to write the image archive
+/// of a build to. Concurrent builds of the same hash get different files, which only the current
+/// user can read. The file is removed when the returned path is dropped.
+///
+/// # Arguments
+/// * `image_hash` - The content hash of the image, used in the file name.
+///
+/// # Returns
+/// * `Ok(TempPath)` pointing at the empty file.
+/// * `Err(anyhow::Error)` if the file could not be created.
+pub fn temporary_archive(image_hash: &str) -> Result<TempPath> {
+    let file = tempfile::Builder::new()
+        .prefix(&format!("dockem-{}-", image_hash))
+        .suffix(".tar")
+        .tempfile()
+        .context("Unable to create a temporary file for the image archive")?;
+    Ok(file.into_temp_path())
+}
+
+/// Saves an image from the Docker daemon to a new `temporary_archive`.
+///
+/// # Arguments
+/// * `docker` - A connected Docker client.
+/// * `image` - The name of the image in the daemon, e.g. `local:<hash>`.
+/// * `image_hash` - The content hash of the image, used in the file name.
+///
+/// # Returns
+/// * `Ok(TempPath)` pointing at the archive, which is removed when it is dropped.
+/// * `Err(anyhow::Error)` if the daemon or the file could not be read or written.
+pub async fn save_docker_image_to_temporary_archive(
+    docker: &Docker,
+    image: &str,
+    image_hash: &str,
+) -> Result<TempPath> {
+    let path = temporary_archive(image_hash)?;
+    save_docker_image(docker, image, &path).await?;
+    Ok(path)
+}
+
+/// Saves an image from the Docker daemon to a tar file, like `docker save` does.
+///
+/// # Arguments
+/// * `docker` - A connected Docker client.
+/// * `image` - The name of the image in the daemon, e.g. `local:<hash>`.
+/// * `path` - The file to write the archive to, see `DockerArchive`.
+///
+/// # Returns
+/// * `Ok(())` if the whole archive was written.
+/// * `Err(anyhow::Error)` if the daemon or the file could not be read or written.
+pub async fn save_docker_image(docker: &Docker, image: &str, path: &Path) -> Result<()> {
+    info!("Saving the image {} from the Docker daemon", image);
+    let mut file = BufWriter::new(
+        File::create(path).with_context(|| format!("Unable to create {}", path.display()))?,
+    );
+    let mut stream = docker.export_image(image);
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.with_context(|| format!("Failed to save the image {}", image))?;
+        file.write_all(&chunk)?;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::temporary_archive;
+
+    #[test]
+    fn test_temporary_archives_are_unique_per_build() {
+        let first = temporary_archive("abc").unwrap();
+        let second = temporary_archive("abc").unwrap();
+        assert_ne!(first.to_path_buf(), second.to_path_buf());
+        assert!(first.exists() && second.exists());
+
+        let path = first.to_path_buf();
+        drop(first);
+        assert!(!path.exists());
+        assert!(second.exists());
+    }
+}
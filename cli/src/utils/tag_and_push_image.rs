@@ -1,25 +1,31 @@
+use crate::utils::{BuildEvent, EventEmitter};
 use bollard::auth::DockerCredentials;
 use bollard::errors::Error as BollardError;
 use bollard::image::{PushImageOptions, TagImageOptions};
 use bollard::Docker;
 use futures_util::stream::StreamExt;
+use tracing::{debug, error, trace};
 
 /// Tags and pushes a Docker image to a registry.
-/// It will print updates to the console and wait for the stream to complete or fail.
+/// It will log updates, emit `BuildEvent::PushProgress` for the uploaded bytes and wait for the
+/// stream to complete or fail. An error reported in the push stream fails the push.
 ///
 /// # Arguments
 /// * `docker` - A connected Docker client.
 /// * `from_image` - The source image name (e.g., `my-image:latest`).
 /// * `to_image` - The target image name (e.g., `my-registry/my-image:latest`).
 /// * `credentials` - Docker credentials for authentication.
+/// * `events` - Receives the push progress and a `BuildEvent::TagPushed` once pushed.
 ///
 /// # Returns
-/// A `Result` indicating success or failure of the tag and push operations.
+/// A `Result` indicating success or failure of the tag and push operations, with
+/// `BollardError::DockerStreamError` if the daemon reported an error while pushing.
 pub async fn tag_and_push_image(
     docker: &Docker,
     from_image: &str,
     to_image: &str,
     credentials: &DockerCredentials,
+    events: &EventEmitter,
 ) -> Result<(), BollardError> {
     // Split `to_image` into repo and tag
     let (repo, tag) = match to_image.split_once(':') {
@@ -28,18 +34,11 @@ pub async fn tag_and_push_image(
     };
 
     // Tag the image
-    let tag_options = TagImageOptions {
-        repo,
-        tag,
-        ..Default::default()
-    };
+    let tag_options = TagImageOptions { repo, tag };
     docker.tag_image(from_image, Some(tag_options)).await?;
 
     // Push the image
-    let push_options = PushImageOptions {
-        tag,
-        ..Default::default()
-    };
+    let push_options = PushImageOptions { tag };
     let mut push_stream = docker.push_image(repo, Some(push_options), Some(credentials.clone()));
 
     // Process the push output
@@ -48,18 +47,34 @@ pub async fn tag_and_push_image(
             Ok(output) => {
                 // Print the status, progress, or error if they exist
                 if let Some(status) = output.status {
-                    println!("Status: {}", status);
+                    debug!(target: "docker", "Status: {}", status);
                 }
                 if let Some(progress) = output.progress {
-                    println!("Progress: {}", progress);
+                    trace!(target: "docker", "Progress: {}", progress);
+                }
+                if let Some(detail) = output.progress_detail {
+                    if let Some(current) = detail.current {
+                        events.emit(BuildEvent::PushProgress {
+                            image: to_image.to_string(),
+                            current: current.max(0) as u64,
+                            total: detail
+                                .total
+                                .filter(|total| *total > 0)
+                                .map(|total| total as u64),
+                        });
+                    }
                 }
                 if let Some(error) = output.error {
-                    eprintln!("Error: {}", error);
+                    error!(target: "docker", "Error: {}", error);
+                    return Err(BollardError::DockerStreamError { error });
                 }
             }
             Err(e) => return Err(e), // Return the error if the push fails
         }
     }
 
+    events.emit(BuildEvent::TagPushed {
+        image: to_image.to_string(),
+    });
     Ok(())
 }
@@ -1,11 +1,18 @@
-use crate::utils::{BuildDockerImageParams, BuildLog, FileGuard};
+use crate::utils::{
+    pin_base_images, BuildDockerImageParams, BuildLog, FileGuard, ResolvedBaseImage,
+    DOCKEM_DIRECTORY,
+};
 use anyhow::{anyhow, Result};
 use flate2::write::GzEncoder;
 use flate2::Compression;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
-use tar::Builder;
+use tar::{Builder, Header};
+use tracing::debug;
+
+/// The name of the Dockerfile with pinned base images inside the build context tarball.
+const PINNED_DOCKERFILE_NAME: &str = ".dockem.Dockerfile";
 
 /// The result of creating the build context tarball.
 pub struct TarBuildContextResult {
@@ -15,15 +22,21 @@ pub struct TarBuildContextResult {
 }
 
 /// Creates a gzipped tarball of the build context, including the Dockerfile and associated files.
+/// With `params.pin_base_images` the Dockerfile is rewritten with the base images pinned to the
+/// digests in `base_images` and added to the tarball as `.dockem.Dockerfile`, nothing
+/// is written to the build context on disk. `.dockem` directories at any depth and the secrets in
+/// `params.secrets` are left out, like they are when hashing.
 ///
 /// # Arguments
 /// * `params` - Params from the user containing settings for the docker build.
+/// * `base_images` - The resolved base images to pin the Dockerfile to.
 /// * `build_log` - An object containing build log parameters.
 ///
 /// # Returns
 /// A `Result` containing the tarball data and the relative path to the Dockerfile.
 pub fn tar_build_context(
     params: &BuildDockerImageParams,
+    base_images: &[ResolvedBaseImage],
     build_log: &mut BuildLog,
 ) -> Result<TarBuildContextResult> {
     // Create path objects
@@ -36,45 +49,63 @@ pub fn tar_build_context(
     }
 
     // Check if the Dockerfile is outside the context directory
-    let not_in_context = match dockerfile_path.clone().strip_prefix(&context_path) {
-        Ok(_) => false,
-        Err(_) => true,
-    };
-    println!(
+    let not_in_context = dockerfile_path.strip_prefix(&context_path).is_err();
+    debug!(
         "Checking if Dockerfile is not in build context: {}, build context: {:?}",
         not_in_context, context_path
     );
-    let (dockerfile_path_buf, dockerfile_guard) =
-        if dockerfile_path.starts_with("../") || not_in_context {
-            // Create a temporary Dockerfile in the build context directory
-            let temp_dockerfile_path = context_path.join("Dockerfile");
-
-            // Copy the contents of the original Dockerfile into the temporary file
-            let mut original_dockerfile = File::open(&dockerfile_path)?;
-            let mut temp_file = File::create(&temp_dockerfile_path)?;
-            io::copy(&mut original_dockerfile, &mut temp_file)?;
-
-            // Mark that we're using a custom Dockerfile
-            build_log.custom_dockerfile = true;
-
-            // Create a guard to clean up the Dockerfile
-            let guard = FileGuard::new(temp_dockerfile_path.clone());
-
-            // Return the relative path to the Dockerfile and the guard
-            (temp_dockerfile_path, Some(guard))
-        } else {
-            // Dockerfile is already in the context directory
-            (dockerfile_path.to_path_buf(), None)
-        };
-
-    println!(
+    let (dockerfile_path_buf, dockerfile_guard) = if params.pin_base_images {
+        // Added to the tarball below
+        build_log.custom_dockerfile = true;
+        (context_path.join(PINNED_DOCKERFILE_NAME), None)
+    } else if dockerfile_path.starts_with("../") || not_in_context {
+        // Create a temporary Dockerfile in the build context directory
+        let temp_dockerfile_path = context_path.join("Dockerfile");
+
+        // Copy the contents of the original Dockerfile into the temporary file
+        let mut original_dockerfile = File::open(&dockerfile_path)?;
+        let mut temp_file = File::create(&temp_dockerfile_path)?;
+        io::copy(&mut original_dockerfile, &mut temp_file)?;
+
+        // Mark that we're using a custom Dockerfile
+        build_log.custom_dockerfile = true;
+
+        // Create a guard to clean up the Dockerfile
+        let guard = FileGuard::new(temp_dockerfile_path.clone());
+
+        // Return the relative path to the Dockerfile and the guard
+        (temp_dockerfile_path, Some(guard))
+    } else {
+        // Dockerfile is already in the context directory
+        (dockerfile_path.to_path_buf(), None)
+    };
+
+    debug!(
         "Creating tarball file with dockerfile path {:?}",
         dockerfile_path_buf.to_string_lossy()
     );
 
     let mut tar_builder = Builder::new(Vec::new());
-    // Add the rest of the build context (recursively)
-    tar_builder.append_dir_all(".", &context_path)?;
+    // Send symlinks as symlinks like the Docker CLI does, this is also how they are hashed
+    tar_builder.follow_symlinks(false);
+    // Add the rest of the build context (recursively), without the secrets inside it
+    let secrets = params.secrets_in(&params.directory);
+    for secret in &secrets {
+        debug!(
+            "Leaving the secret {} out of the build context",
+            secret.display()
+        );
+    }
+    append_context(&mut tar_builder, &context_path, Path::new(""), &secrets)?;
+    if params.pin_base_images {
+        let dockerfile = fs::read_to_string(&dockerfile_path)?;
+        let pinned = pin_base_images(&dockerfile, base_images);
+        let mut header = Header::new_gnu();
+        header.set_size(pinned.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar_builder.append_data(&mut header, PINNED_DOCKERFILE_NAME, pinned.as_bytes())?;
+    }
     // Finish the tarball
     let tar_data = tar_builder.into_inner()?;
 
@@ -82,7 +113,7 @@ pub fn tar_build_context(
     let mut gz_encoder = GzEncoder::new(Vec::new(), Compression::default());
     gz_encoder.write_all(&tar_data)?;
     let gz_data = gz_encoder.finish()?;
-    println!("Successfully compressed context into tarball");
+    debug!("Successfully compressed context into tarball");
 
     // Return the tarball data and the Dockerfile path
     Ok(TarBuildContextResult {
@@ -96,3 +127,146 @@ pub fn tar_build_context(
         _dockerfile_guard: dockerfile_guard,
     })
 }
+
+/// Appends the entries of a directory in the build context to the tarball, sorted by name and
+/// without `.dockem` directories or the `excluded` paths.
+fn append_context(
+    tar_builder: &mut Builder<Vec<u8>>,
+    context_path: &Path,
+    relative: &Path,
+    excluded: &[PathBuf],
+) -> Result<()> {
+    let mut entries = fs::read_dir(context_path.join(relative))?
+        .map(|entry| entry.map(|entry| entry.file_name()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    for name in entries {
+        let relative = relative.join(&name);
+        if name == DOCKEM_DIRECTORY || excluded.contains(&relative) {
+            continue;
+        }
+        let path = context_path.join(&relative);
+        tar_builder.append_path_with_name(&path, &relative)?;
+        if fs::symlink_metadata(&path)?.is_dir() {
+            append_context(tar_builder, context_path, &relative, excluded)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{
+        tar_build_context, BuildDockerImageParams, BuildLog, BuildSecret, ResolvedBaseImage,
+    };
+    use flate2::read::GzDecoder;
+    use std::fs;
+    use std::io::Read;
+    use tar::Archive;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_pinned_dockerfile_is_added_to_the_tarball() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let context = temp_dir.path();
+        fs::write(context.join("Dockerfile"), "FROM node:20\nCOPY . /app\n").unwrap();
+        let params = BuildDockerImageParams::builder("my-org/app")
+            .directory(context.to_str().unwrap())
+            .dockerfile_path(context.join("Dockerfile").to_str().unwrap())
+            .pin_base_images(true)
+            .build()
+            .unwrap();
+        let base_images = [ResolvedBaseImage {
+            image: "node:20".to_string(),
+            reference: "docker.io/library/node:20".to_string(),
+            digest: "sha256:aaa".to_string(),
+        }];
+
+        let result = tar_build_context(&params, &base_images, &mut BuildLog::default()).unwrap();
+        let dockerfile_path = result.dockerfile_path.clone().unwrap();
+        assert_eq!(dockerfile_path.to_str(), Some(".dockem.Dockerfile"));
+
+        let mut archive = Archive::new(GzDecoder::new(result.tarball.as_slice()));
+        let mut pinned = String::new();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            if entry.path().unwrap().ends_with(".dockem.Dockerfile") {
+                entry.read_to_string(&mut pinned).unwrap();
+            }
+        }
+        assert_eq!(pinned, "FROM node:20@sha256:aaa\nCOPY . /app\n");
+        // The Dockerfile in the build context is left alone
+        assert_eq!(
+            fs::read_to_string(context.join("Dockerfile")).unwrap(),
+            "FROM node:20\nCOPY . /app\n"
+        );
+    }
+
+    #[test]
+    fn test_dockem_directories_are_not_sent_to_docker() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let context = temp_dir.path();
+        fs::write(context.join("Dockerfile"), "FROM node:20\nCOPY . /app\n").unwrap();
+        fs::create_dir_all(context.join(".dockem/cache")).unwrap();
+        fs::create_dir_all(context.join("src/.dockem/cache")).unwrap();
+        fs::write(context.join(".dockem/cache/file-digests"), "x").unwrap();
+        fs::write(context.join("src/.dockem/cache/file-digests"), "x").unwrap();
+        fs::write(context.join("src/index.js"), "console.log()").unwrap();
+        let params = BuildDockerImageParams::builder("my-org/app")
+            .directory(context.to_str().unwrap())
+            .dockerfile_path(context.join("Dockerfile").to_str().unwrap())
+            .build()
+            .unwrap();
+
+        let result = tar_build_context(&params, &[], &mut BuildLog::default()).unwrap();
+        let mut archive = Archive::new(GzDecoder::new(result.tarball.as_slice()));
+        let paths: Vec<String> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                entry
+                    .unwrap()
+                    .path()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        assert_eq!(paths, vec!["Dockerfile", "src", "src/index.js"]);
+    }
+
+    #[test]
+    fn test_secrets_are_not_sent_to_docker() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let context = temp_dir.path();
+        fs::write(context.join("Dockerfile"), "FROM node:20\nCOPY . /app\n").unwrap();
+        fs::create_dir_all(context.join("config")).unwrap();
+        fs::write(context.join(".npmrc"), "//registry/:_authToken=abc").unwrap();
+        fs::write(context.join("config/token"), "abc").unwrap();
+        fs::write(context.join("config/app.json"), "{}").unwrap();
+        let params = BuildDockerImageParams::builder("my-org/app")
+            .directory(context.to_str().unwrap())
+            .dockerfile_path(context.join("Dockerfile").to_str().unwrap())
+            .secret(BuildSecret::new("npmrc", context.join(".npmrc")))
+            .secret(BuildSecret::new("token", context.join("config/token")))
+            .build()
+            .unwrap();
+
+        let result = tar_build_context(&params, &[], &mut BuildLog::default()).unwrap();
+        let mut archive = Archive::new(GzDecoder::new(result.tarball.as_slice()));
+        let paths: Vec<String> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                entry
+                    .unwrap()
+                    .path()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        assert_eq!(paths, vec!["Dockerfile", "config", "config/app.json"]);
+    }
+}
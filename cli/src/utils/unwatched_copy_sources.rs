@@ -0,0 +1,91 @@
+use crate::utils::{project_relative_path, BuildDockerImageParams, CopySource, Dockerfile};
+use anyhow::Result;
+use std::path::Path;
+
+/// Finds the `COPY` and `ADD` sources that no watch file or watch directory covers. This only
+/// matters when the build directory is not hashed, because a change to such a source would
+/// then not trigger a rebuild. Sources that do not exist or are outside the build directory are
+/// left out, Docker reports those itself.
+///
+/// # Arguments
+/// * `params` - The build parameters naming the Dockerfile, build directory and watches.
+/// * `root` - The canonical path of the project root.
+///
+/// # Returns
+/// * `Ok(Vec<CopySource>)` containing the sources that are not watched.
+/// * `Err(anyhow::Error)` if the Dockerfile cannot be read.
+pub fn unwatched_copy_sources(
+    params: &BuildDockerImageParams,
+    root: &Path,
+) -> Result<Vec<CopySource>> {
+    let dockerfile = Dockerfile::read(&params.dockerfile_path)?;
+    let project_paths = |paths: Option<&Vec<String>>| -> Vec<String> {
+        paths
+            .into_iter()
+            .flatten()
+            .filter_map(|path| project_relative_path(root, path).ok())
+            .collect()
+    };
+    let watch_files = project_paths(params.watch_file.as_ref());
+    let watch_directories = project_paths(params.watch_directory.as_ref());
+
+    Ok(dockerfile
+        .copy_sources()
+        .into_iter()
+        .filter(|source| {
+            let Some(path) = source.context_path() else {
+                return false;
+            };
+            let full_path = Path::new(&params.directory).join(path);
+            let Ok(project_path) = project_relative_path(root, &full_path.to_string_lossy()) else {
+                return false;
+            };
+
+            let watched_file = watch_files.contains(&project_path);
+            let in_watched_directory = watch_directories.iter().any(|directory| {
+                directory == "."
+                    || project_path == *directory
+                    || project_path.starts_with(&format!("{}/", directory))
+            });
+            !watched_file && !in_watched_directory
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{unwatched_copy_sources, BuildDockerImageParams};
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_sources_outside_the_watches_are_reported() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let root = fs::canonicalize(temp_dir.path()).unwrap();
+        fs::create_dir_all(root.join("app/src")).unwrap();
+        fs::create_dir_all(root.join("app/config")).unwrap();
+        fs::write(root.join("app/package.json"), "{}").unwrap();
+        fs::write(
+            root.join("app/Dockerfile"),
+            "FROM node:20\n\
+             COPY package.json ./\n\
+             COPY src/ ./src/\n\
+             COPY config ./config\n",
+        )
+        .unwrap();
+        let path = |relative: &str| root.join(relative).to_str().unwrap().to_string();
+        let params = BuildDockerImageParams::builder("my-org/app")
+            .directory(path("app"))
+            .dockerfile_path(path("app/Dockerfile"))
+            .ignore_build_directory(true)
+            .watch_file(path("app/package.json"))
+            .watch_directory(path("app/src"))
+            .build()
+            .unwrap();
+
+        let unwatched = unwatched_copy_sources(&params, &root).unwrap();
+        assert_eq!(unwatched.len(), 1);
+        assert_eq!(unwatched[0].source, "config");
+        assert_eq!(unwatched[0].line, 4);
+    }
+}
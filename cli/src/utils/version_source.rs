@@ -0,0 +1,79 @@
+use crate::utils::extract_version::extract_version;
+use crate::utils::DockemError;
+use std::fmt;
+
+/// Provides the version that is appended to the output tags, e.g. `alpha-v1.0.0`.
+///
+/// Implement this to take the version from somewhere other than a JSON file, such as a git tag
+/// or a release tool. The version is resolved once per build on a blocking thread.
+pub trait VersionSource: fmt::Debug + Send + Sync {
+    /// Returns the version exactly as it should appear in the tags, including any `v` prefix.
+    fn version(&self) -> Result<String, DockemError>;
+}
+
+/// Reads the `version` key from a JSON file (e.g. `package.json`) and prefixes it with `v`.
+/// This is what the `--version-file` flag uses.
+#[derive(Debug, Clone)]
+pub struct JsonVersionFile {
+    path: String,
+}
+
+impl JsonVersionFile {
+    /// Creates a version source for the JSON file at the given path.
+    pub fn new(path: impl Into<String>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// The path to the JSON file.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl VersionSource for JsonVersionFile {
+    fn version(&self) -> Result<String, DockemError> {
+        extract_version(&self.path).map_err(|error| DockemError::Version(error.into()))
+    }
+}
+
+/// A version that is already known, it is used as is without adding a `v` prefix.
+#[derive(Debug, Clone)]
+pub struct StaticVersion(pub String);
+
+impl VersionSource for StaticVersion {
+    fn version(&self) -> Result<String, DockemError> {
+        Ok(self.0.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{DockemError, JsonVersionFile, StaticVersion, VersionSource};
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_json_version_file_adds_prefix() {
+        let mut version_file = NamedTempFile::new().expect("Failed to create temp file");
+        write!(version_file, r#"{{"name": "backend", "version": "1.2.3"}}"#)
+            .expect("Failed to write temp file");
+
+        let source = JsonVersionFile::new(version_file.path().to_str().unwrap());
+        assert_eq!(source.version().unwrap(), "v1.2.3");
+    }
+
+    #[test]
+    fn test_json_version_file_without_version_key() {
+        let mut version_file = NamedTempFile::new().expect("Failed to create temp file");
+        write!(version_file, r#"{{"name": "backend"}}"#).expect("Failed to write temp file");
+
+        let source = JsonVersionFile::new(version_file.path().to_str().unwrap());
+        assert!(matches!(source.version(), Err(DockemError::Version(_))));
+    }
+
+    #[test]
+    fn test_static_version_is_used_as_is() {
+        let source = StaticVersion("2024.06.1".to_string());
+        assert_eq!(source.version().unwrap(), "2024.06.1");
+    }
+}
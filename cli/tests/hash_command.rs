@@ -0,0 +1,44 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+/// Runs `dockem-rs hash` from the given working directory and returns the printed hash.
+fn hash_from(working_directory: &Path, project: &Path) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_dockem-rs"))
+        .current_dir(working_directory)
+        .args(["hash", "--no-cache", "--directory"])
+        .arg(project.join("apps/backend"))
+        .arg("--dockerfile-path")
+        .arg(project.join("apps/backend/Dockerfile"))
+        .arg("--watch-directory")
+        .arg(project.join("libs/shared"))
+        .output()
+        .expect("Failed to run dockem-rs");
+    assert!(
+        output.status.success(),
+        "dockem-rs hash failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+#[test]
+fn test_hash_does_not_depend_on_the_working_directory() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let project = fs::canonicalize(temp_dir.path()).unwrap().join("project");
+    fs::create_dir_all(project.join("apps/backend")).unwrap();
+    fs::create_dir_all(project.join("libs/shared")).unwrap();
+    fs::create_dir_all(temp_dir.path().join("elsewhere")).unwrap();
+    fs::write(project.join("apps/backend/Dockerfile"), "FROM node:20").unwrap();
+    fs::write(project.join("apps/backend/index.js"), "console.log('hi')").unwrap();
+    fs::write(project.join("libs/shared/index.js"), "export {}").unwrap();
+
+    let from_project = hash_from(&project, &project);
+    let from_backend = hash_from(&project.join("apps/backend"), &project);
+    let from_elsewhere = hash_from(&temp_dir.path().join("elsewhere"), &project);
+
+    assert!(from_project.starts_with("h2-"), "{}", from_project);
+    assert_eq!(from_project, from_backend);
+    assert_eq!(from_project, from_elsewhere);
+}